@@ -1,9 +1,17 @@
-use snowboard::{response, Request, ResponseLike, Result, Server};
+use snowboard::{response, Method, Request, Response, ResponseLike, Result, Server};
+use std::{io, path::Path};
 
 fn router(req: Request) -> impl ResponseLike {
+	let url = req.parse_url();
+
 	// /{x}
-	match req.parse_url().at(0) {
+	match url.at(0) {
 		Some("ping") => response!(ok, "Pong!"),
+		// /posts?page=2
+		Some("posts") => {
+			let page: u32 = url.search_param_as("page").unwrap_or(1);
+			response!(ok, format!("Page {}", page))
+		}
 		Some("api") => response!(not_implemented, "👀"),
 		None => response!(ok, "Hello, world!"),
 		_ => response!(not_found, "Route not found"),
@@ -11,5 +19,20 @@ fn router(req: Request) -> impl ResponseLike {
 }
 
 fn main() -> Result {
-	Server::new("localhost:8080")?.run(router);
+	Server::new("localhost:8080")?
+		// /users/{id}, tried before `router`
+		.route(Method::GET, "/users/:id", |_, params| {
+			response!(ok, format!("User {}", params["id"]))
+		})
+		// /static/{file path}, served from the `static` folder
+		.route(Method::GET, "/static/*path", |req, params| {
+			// Don't serve files outside of the folder.
+			if params["path"].split('/').any(|segment| segment == "..") {
+				return Err(io::Error::from(io::ErrorKind::NotFound));
+			}
+
+			// Supports `Range` requests, for seeking through media.
+			Ok(Response::file(Path::new("static").join(&params["path"]))?.with_range(&req))
+		})
+		.run(router);
 }
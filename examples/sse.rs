@@ -0,0 +1,29 @@
+use snowboard::{response, Response, Server};
+use std::{sync::mpsc, thread, time::Duration};
+
+fn handle_events() -> Response {
+	let (sender, receiver) = mpsc::channel();
+
+	thread::spawn(move || {
+		for i in 0.. {
+			// The client disconnected.
+			if sender.send(("tick".into(), i.to_string())).is_err() {
+				break;
+			}
+
+			thread::sleep(Duration::from_secs(1));
+		}
+	});
+
+	Response::event_stream(receiver)
+}
+
+fn main() -> snowboard::Result {
+	Server::new("localhost:3000")?.run(|req| {
+		if req.url == "/events" {
+			handle_events()
+		} else {
+			response!(ok, "Try `curl -N localhost:3000/events`!")
+		}
+	})
+}
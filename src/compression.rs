@@ -0,0 +1,115 @@
+//! A module that provides response compression, negotiated using the `Accept-Encoding` header.
+
+use std::io::{self, Write};
+
+use flate2::write::{GzEncoder, ZlibEncoder};
+
+/// A content encoding (compression algorithm) supported by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+	/// `br`, using brotli.
+	Brotli,
+	/// `gzip`.
+	Gzip,
+	/// `deflate`, the zlib format (RFC 1950).
+	Deflate,
+}
+
+/// Brotli quality used to compress responses, balancing speed and size.
+const BROTLI_QUALITY: u32 = 5;
+
+/// Brotli window size (log2) used to compress responses.
+const BROTLI_WINDOW: u32 = 22;
+
+/// Size of the internal buffer used by the brotli encoder.
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+impl Encoding {
+	/// The default priority used when the client values encodings equally.
+	pub const DEFAULT_PRIORITY: [Encoding; 3] =
+		[Encoding::Brotli, Encoding::Gzip, Encoding::Deflate];
+
+	/// Gets the name of the encoding, as used in `Accept-Encoding` and `Content-Encoding`.
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Encoding::Brotli => "br",
+			Encoding::Gzip => "gzip",
+			Encoding::Deflate => "deflate",
+		}
+	}
+
+	/// Compresses `bytes` using this encoding.
+	pub fn encode(&self, bytes: &[u8]) -> io::Result<Vec<u8>> {
+		match self {
+			Encoding::Brotli => {
+				let mut encoder = brotli::CompressorWriter::new(
+					Vec::new(),
+					BROTLI_BUFFER_SIZE,
+					BROTLI_QUALITY,
+					BROTLI_WINDOW,
+				);
+
+				encoder.write_all(bytes)?;
+				// The stream is finished when the writer is consumed.
+				Ok(encoder.into_inner())
+			}
+			Encoding::Gzip => {
+				let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+				encoder.write_all(bytes)?;
+				encoder.finish()
+			}
+			Encoding::Deflate => {
+				let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+				encoder.write_all(bytes)?;
+				encoder.finish()
+			}
+		}
+	}
+
+	/// Picks the encoding the client prefers from an `Accept-Encoding` header value,
+	/// among the ones in `priority`. Encodings with the same quality value are
+	/// picked in the order of `priority`.
+	///
+	/// Returns `None` if the client accepts none of them (`q=0` or not listed).
+	pub fn negotiate(accept_encoding: &str, priority: &[Encoding]) -> Option<Encoding> {
+		let mut best: Option<(Encoding, f32)> = None;
+
+		for encoding in priority {
+			let quality = quality_of(accept_encoding, encoding.as_str());
+
+			match best {
+				Some((_, best_quality)) if best_quality >= quality => {}
+				_ if quality > 0.0 => best = Some((*encoding, quality)),
+				_ => {}
+			}
+		}
+
+		best.map(|(encoding, _)| encoding)
+	}
+}
+
+/// Gets the quality value given to `coding` in an `Accept-Encoding` header value.
+/// `*` is used for codings that aren't listed, and unlisted codings get `0`.
+fn quality_of(accept_encoding: &str, coding: &str) -> f32 {
+	let mut wildcard = 0.0;
+
+	for item in accept_encoding.split(',') {
+		let mut parts = item.split(';');
+		let name = parts.next().unwrap_or("").trim();
+
+		let quality = parts
+			.filter_map(|param| param.trim().strip_prefix("q="))
+			.find_map(|q| q.trim().parse::<f32>().ok())
+			.unwrap_or(1.0);
+
+		if name.eq_ignore_ascii_case(coding) {
+			return quality;
+		}
+
+		if name == "*" {
+			wildcard = quality;
+		}
+	}
+
+	wildcard
+}
@@ -0,0 +1,147 @@
+//! A module that provides a builder for `Set-Cookie` headers.
+
+use std::fmt;
+
+/// The `SameSite` attribute of a cookie, restricting cross-site requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SameSite {
+	/// Only sent in requests from the same site.
+	Strict,
+	/// Also sent when navigating to the site from another one.
+	Lax,
+	/// Sent in every request. Browsers require `Secure` cookies for this.
+	None,
+}
+
+impl fmt::Display for SameSite {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let text = match self {
+			SameSite::Strict => "Strict",
+			SameSite::Lax => "Lax",
+			SameSite::None => "None",
+		};
+
+		write!(f, "{}", text)
+	}
+}
+
+/// A cookie set by a response, used with `Response::add_cookie`.
+/// Its `Display` implementation formats it as a `Set-Cookie` header value.
+///
+/// # Example
+/// ```rust
+/// use snowboard::{response, Cookie, SameSite};
+///
+/// let cookie = Cookie::new("session", "abc123")
+///     .with_path("/")
+///     .with_max_age(3600)
+///     .with_http_only()
+///     .with_secure()
+///     .with_same_site(SameSite::Lax);
+///
+/// let mut res = response!(ok);
+/// res.add_cookie(&cookie);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+	/// Name of the cookie.
+	name: String,
+	/// Value of the cookie, sent as is.
+	value: String,
+	/// Path the cookie is sent to.
+	path: Option<String>,
+	/// Domain the cookie is sent to.
+	domain: Option<String>,
+	/// How long (in seconds) the cookie is kept.
+	max_age: Option<u64>,
+	/// Whether the cookie is hidden from scripts.
+	http_only: bool,
+	/// Whether the cookie is only sent over HTTPS.
+	secure: bool,
+	/// Whether the cookie is sent in cross-site requests.
+	same_site: Option<SameSite>,
+}
+
+impl Cookie {
+	/// Creates a cookie with no attributes. The value is not encoded.
+	pub fn new(name: &str, value: &str) -> Self {
+		Self {
+			name: name.into(),
+			value: value.into(),
+			path: None,
+			domain: None,
+			max_age: None,
+			http_only: false,
+			secure: false,
+			same_site: None,
+		}
+	}
+
+	/// Sets the path the cookie is sent to.
+	pub fn with_path(mut self, path: &str) -> Self {
+		self.path = Some(path.into());
+		self
+	}
+
+	/// Sets the domain the cookie is sent to.
+	pub fn with_domain(mut self, domain: &str) -> Self {
+		self.domain = Some(domain.into());
+		self
+	}
+
+	/// Sets how long (in seconds) the cookie is kept. `0` deletes it.
+	pub fn with_max_age(mut self, seconds: u64) -> Self {
+		self.max_age = Some(seconds);
+		self
+	}
+
+	/// Hides the cookie from scripts (`HttpOnly`).
+	pub fn with_http_only(mut self) -> Self {
+		self.http_only = true;
+		self
+	}
+
+	/// Only sends the cookie over HTTPS (`Secure`).
+	pub fn with_secure(mut self) -> Self {
+		self.secure = true;
+		self
+	}
+
+	/// Sets the `SameSite` attribute.
+	pub fn with_same_site(mut self, same_site: SameSite) -> Self {
+		self.same_site = Some(same_site);
+		self
+	}
+}
+
+impl fmt::Display for Cookie {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}={}", self.name, self.value)?;
+
+		if let Some(path) = &self.path {
+			write!(f, "; Path={}", path)?;
+		}
+
+		if let Some(domain) = &self.domain {
+			write!(f, "; Domain={}", domain)?;
+		}
+
+		if let Some(max_age) = self.max_age {
+			write!(f, "; Max-Age={}", max_age)?;
+		}
+
+		if self.http_only {
+			write!(f, "; HttpOnly")?;
+		}
+
+		if self.secure {
+			write!(f, "; Secure")?;
+		}
+
+		if let Some(same_site) = self.same_site {
+			write!(f, "; SameSite={}", same_site)?;
+		}
+
+		Ok(())
+	}
+}
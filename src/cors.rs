@@ -0,0 +1,165 @@
+//! A module that provides Cross-Origin Resource Sharing (CORS) support for the server.
+
+use crate::{Method, Request, Response};
+
+/// CORS configuration, used with `Server::with_cors`.
+///
+/// By default, any origin is allowed to use `GET`, `HEAD` and `POST`.
+///
+/// # Example
+/// ```rust
+/// use snowboard::{Cors, Method, Server};
+///
+/// let cors = Cors::default()
+///     .with_origins(&["https://example.com"])
+///     .with_methods(&[Method::GET, Method::PUT])
+///     .with_headers(&["Content-Type"])
+///     .with_max_age(3600);
+///
+/// let server = Server::new("localhost:8080").expect("failed to start server").with_cors(cors);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cors {
+	/// Allowed origins. `*` allows any origin.
+	allow_origins: Vec<String>,
+	/// Allowed methods, sent in preflight responses.
+	allow_methods: Vec<Method>,
+	/// Allowed request headers, sent in preflight responses.
+	allow_headers: Vec<String>,
+	/// How long (in seconds) browsers can cache preflight responses.
+	max_age: Option<u64>,
+	/// Whether requests can include credentials (cookies, etc).
+	allow_credentials: bool,
+}
+
+impl Default for Cors {
+	fn default() -> Self {
+		Self {
+			allow_origins: vec!["*".into()],
+			allow_methods: vec![Method::GET, Method::HEAD, Method::POST],
+			allow_headers: vec![],
+			max_age: None,
+			allow_credentials: false,
+		}
+	}
+}
+
+impl Cors {
+	/// Sets the allowed origins. Use `*` to allow any origin.
+	pub fn with_origins(mut self, origins: &[&str]) -> Self {
+		self.allow_origins = origins.iter().map(|o| o.to_string()).collect();
+		self
+	}
+
+	/// Sets the allowed methods.
+	pub fn with_methods(mut self, methods: &[Method]) -> Self {
+		self.allow_methods = methods.to_vec();
+		self
+	}
+
+	/// Sets the allowed request headers.
+	pub fn with_headers(mut self, headers: &[&str]) -> Self {
+		self.allow_headers = headers.iter().map(|h| h.to_string()).collect();
+		self
+	}
+
+	/// Sets how long (in seconds) browsers can cache preflight responses.
+	pub fn with_max_age(mut self, seconds: u64) -> Self {
+		self.max_age = Some(seconds);
+		self
+	}
+
+	/// Allows requests to include credentials, like cookies.
+	pub fn with_credentials(mut self) -> Self {
+		self.allow_credentials = true;
+		self
+	}
+
+	/// Answers a preflight request (`OPTIONS` with `Origin` and
+	/// `Access-Control-Request-Method`) with a `204 No Content`.
+	/// Returns `None` if the request is not a preflight request.
+	pub fn preflight(&self, req: &Request) -> Option<Response> {
+		if !req.is_preflight() {
+			return None;
+		}
+
+		let origin = req.origin()?;
+		let mut res = crate::response!(no_content);
+
+		// Disallowed origins get no CORS headers at all.
+		if !self.allows(origin) {
+			return Some(res);
+		}
+
+		res = self.apply(Some(origin), res);
+
+		let methods = self
+			.allow_methods
+			.iter()
+			.map(Method::to_string)
+			.collect::<Vec<String>>()
+			.join(", ");
+
+		res.set_header("Access-Control-Allow-Methods", methods);
+
+		if !self.allow_headers.is_empty() {
+			res.set_header(
+				"Access-Control-Allow-Headers",
+				self.allow_headers.join(", "),
+			);
+		}
+
+		if let Some(max_age) = self.max_age {
+			res.set_header("Access-Control-Max-Age", max_age.to_string());
+		}
+
+		Some(res)
+	}
+
+	/// Adds the CORS headers to a response for a request with the given `Origin` header.
+	/// Nothing is added if there's no origin or it's not allowed.
+	pub fn apply(&self, origin: Option<&str>, mut res: Response) -> Response {
+		let origin = match origin {
+			Some(origin) if self.allows(origin) => origin,
+			_ => return res,
+		};
+
+		if self.allows_any() && !self.allow_credentials {
+			res.set_header("Access-Control-Allow-Origin", "*".into());
+			return res;
+		}
+
+		// `*` can't be used with credentials, so the origin is sent back instead.
+		// Caches are told so, keeping whatever the response already varies by.
+		let vary = match res.headers.as_ref().and_then(|h| h.get("Vary")) {
+			Some(vary)
+				if vary
+					.split(',')
+					.any(|v| v.trim().eq_ignore_ascii_case("origin")) =>
+			{
+				vary.clone()
+			}
+			Some(vary) => format!("{}, Origin", vary),
+			None => "Origin".into(),
+		};
+
+		res.set_header("Access-Control-Allow-Origin", origin.into())
+			.set_header("Vary", vary);
+
+		if self.allow_credentials {
+			res.set_header("Access-Control-Allow-Credentials", "true".into());
+		}
+
+		res
+	}
+
+	/// Checks if any origin is allowed.
+	fn allows_any(&self) -> bool {
+		self.allow_origins.iter().any(|o| o == "*")
+	}
+
+	/// Checks if an origin is allowed.
+	fn allows(&self, origin: &str) -> bool {
+		self.allows_any() || self.allow_origins.iter().any(|o| o == origin)
+	}
+}
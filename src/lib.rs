@@ -3,9 +3,16 @@
 #![warn(clippy::cognitive_complexity, rust_2018_idioms)]
 #![doc = include_str!("../README.md")]
 
+#[cfg(feature = "compression")]
+mod compression;
+mod cookie;
+mod cors;
 mod macros;
+mod multipart;
+mod range;
 mod request;
 mod response;
+mod router;
 mod server;
 mod url;
 mod util;
@@ -13,19 +20,34 @@ mod util;
 #[cfg(feature = "websocket")]
 mod ws;
 
+#[cfg(feature = "compression")]
+pub use compression::Encoding;
+pub use cookie::{Cookie, SameSite};
+pub use cors::Cors;
+pub use multipart::Part;
+pub use range::Range;
 pub use request::Request;
-pub use response::{Headers, Response, ResponseLike, DEFAULT_HTTP_VERSION};
-pub use server::{Server, Stream, DEFAULT_BUFFER_SIZE};
-pub use url::Url;
-pub use util::{HttpVersion, Method};
+pub use response::{
+	reason_phrase, Body, BodyReader, Headers, Response, ResponseBuilder, ResponseLike,
+	DEFAULT_HTTP_VERSION,
+};
+pub use router::{Params, Router};
+pub use server::{
+	ConnectionLimit, ReloadableHandler, Server, Stream, StreamExt, TlsInfo, DEFAULT_BUFFER_SIZE,
+};
+pub use url::{OwnedUrl, Url};
+pub use util::{mime_from_extension, HttpVersion, Method};
 
 #[cfg(feature = "websocket")]
 /// A WebSocket connection.
 pub type WebSocket<'a> = tungstenite::WebSocket<&'a mut Stream>;
 
+#[cfg(feature = "json")]
+pub use response::Json;
+
 #[cfg(feature = "tls")]
 // Re-export needed structs for `Server::new(...)` with TLS.
-pub use native_tls::{Identity, TlsAcceptor};
+pub use native_tls::{Identity, Protocol as TlsVersion, TlsAcceptor};
 
 /// A type alias for `std::io::Result<()>`
 /// used in `Server::new()?.run(...)`.
@@ -0,0 +1,110 @@
+//! A module that provides code to parse `multipart/form-data` bodies, used for file uploads.
+
+/// A part of a `multipart/form-data` body.
+/// See [`crate::Request::multipart`].
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Part {
+	/// Name of the form field.
+	pub name: String,
+	/// Name of the uploaded file, if the part is a file.
+	pub filename: Option<String>,
+	/// Content type of the part, if specified.
+	pub content_type: Option<String>,
+	/// Contents of the part, untouched.
+	pub bytes: Vec<u8>,
+}
+
+impl Part {
+	/// Parses a part from its raw headers and contents.
+	/// Returns `None` if there's no `Content-Disposition` header with a name.
+	fn parse(raw: &[u8]) -> Option<Self> {
+		let head_end = find(raw, b"\r\n\r\n")?;
+		let head = std::str::from_utf8(&raw[..head_end]).ok()?;
+
+		let mut name = None;
+		let mut filename = None;
+		let mut content_type = None;
+
+		for line in head.split("\r\n") {
+			let (key, value) = match line.split_once(':') {
+				Some(header) => header,
+				None => continue,
+			};
+
+			if key.trim().eq_ignore_ascii_case("Content-Disposition") {
+				for param in value.split(';').skip(1) {
+					match param.split_once('=') {
+						Some((k, v)) if k.trim() == "name" => name = Some(unquote(v)),
+						Some((k, v)) if k.trim() == "filename" => filename = Some(unquote(v)),
+						_ => {}
+					}
+				}
+			} else if key.trim().eq_ignore_ascii_case("Content-Type") {
+				content_type = Some(value.trim().to_string());
+			}
+		}
+
+		Some(Self {
+			name: name?,
+			filename,
+			content_type,
+			bytes: raw[head_end + 4..].to_vec(),
+		})
+	}
+}
+
+/// Parses a `multipart/form-data` body using the given boundary.
+/// Returns `None` if the body is malformed.
+pub(crate) fn parse(body: &[u8], boundary: &str) -> Option<Vec<Part>> {
+	if boundary.is_empty() {
+		return None;
+	}
+
+	let delimiter = format!("--{}", boundary);
+	let separator = format!("\r\n{}", delimiter);
+
+	let mut rest = &body[find(body, delimiter.as_bytes())? + delimiter.len()..];
+	let mut parts = vec![];
+
+	loop {
+		// The last delimiter ends with `--`.
+		if rest.starts_with(b"--") {
+			return Some(parts);
+		}
+
+		rest = rest.strip_prefix(b"\r\n")?;
+
+		let end = find(rest, separator.as_bytes())?;
+		parts.push(Part::parse(&rest[..end])?);
+
+		rest = &rest[end + separator.len()..];
+	}
+}
+
+/// Gets the boundary from a `multipart/form-data` content type.
+pub(crate) fn boundary(content_type: &str) -> Option<&str> {
+	let mut params = content_type.split(';');
+	let mime = params.next()?.trim();
+
+	if !mime.eq_ignore_ascii_case("multipart/form-data") {
+		return None;
+	}
+
+	params
+		.filter_map(|param| param.split_once('='))
+		.find(|(key, _)| key.trim().eq_ignore_ascii_case("boundary"))
+		.map(|(_, value)| value.trim().trim_matches('"'))
+}
+
+/// Finds the position of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+	haystack
+		.windows(needle.len())
+		.position(|window| window == needle)
+}
+
+/// Removes surrounding whitespace and double quotes from a parameter value.
+fn unquote(value: &str) -> String {
+	value.trim().trim_matches('"').to_string()
+}
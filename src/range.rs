@@ -0,0 +1,56 @@
+//! A module that provides code to handle byte ranges, used for partial content.
+
+/// A single byte range, as requested using the `Range` header.
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Range {
+	/// `bytes=start-end`, both inclusive.
+	Bounded(u64, u64),
+	/// `bytes=start-`, from `start` to the end.
+	From(u64),
+	/// `bytes=-len`, the last `len` bytes.
+	Suffix(u64),
+}
+
+impl Range {
+	/// Parses the value of a `Range` header with a single byte range, like `bytes=0-99`.
+	///
+	/// Returns `None` for other units, invalid ranges, and headers with several
+	/// ranges (which aren't supported, so the whole resource can be sent instead).
+	pub fn parse(value: &str) -> Option<Self> {
+		let (unit, spec) = value.trim().split_once('=')?;
+
+		if !unit.trim().eq_ignore_ascii_case("bytes") || spec.contains(',') {
+			return None;
+		}
+
+		let (start, end) = spec.split_once('-')?;
+
+		match (start.trim(), end.trim()) {
+			("", "") => None,
+			("", suffix) => suffix.parse().ok().map(Range::Suffix),
+			(start, "") => start.parse().ok().map(Range::From),
+			(start, end) => match (start.parse().ok()?, end.parse().ok()?) {
+				(start, end) if start <= end => Some(Range::Bounded(start, end)),
+				_ => None,
+			},
+		}
+	}
+
+	/// Gets the inclusive `(start, end)` positions of the range for a resource
+	/// of `len` bytes, or `None` if the range can't be satisfied.
+	pub fn resolve(&self, len: u64) -> Option<(u64, u64)> {
+		if len == 0 {
+			return None;
+		}
+
+		match *self {
+			Range::Bounded(start, end) if start <= end && start < len => {
+				Some((start, end.min(len - 1)))
+			}
+			Range::From(start) if start < len => Some((start, len - 1)),
+			Range::Suffix(suffix) if suffix > 0 => Some((len - suffix.min(len), len - 1)),
+			_ => None,
+		}
+	}
+}
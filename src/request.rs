@@ -1,9 +1,11 @@
 //! A module that provides code to handle https/http requests.
 
+use std::borrow::Cow;
+use std::collections::{hash_map::Entry, HashMap};
 use std::net::SocketAddr;
-use std::{borrow::Cow, collections::HashMap};
 
-use crate::{Method, Url};
+use crate::url::percent_decode;
+use crate::{HttpVersion, Method, OwnedUrl, Part, Url};
 
 #[cfg(feature = "json")]
 use crate::ResponseLike;
@@ -20,53 +22,137 @@ pub struct Request {
 	pub url: String,
 	/// Method used in the request. Might be Method::Unknown if parsing fails.
 	pub method: Method,
+	/// HTTP version from the request line. Might be `HttpVersion::UNKNOWN` if parsing fails.
+	pub version: HttpVersion,
 	/// Body of the request, in bytes.
 	/// Use [`Request::text`], [`Request::json`], or [`Request::force_json`]
 	/// to get a parsed version of the body.
+	///
+	/// Chunked bodies (`Transfer-Encoding: chunked`) are decoded, replacing that
+	/// header with a `Content-Length`. Their trailers are kept apart from the headers,
+	/// so they can't change how the request was framed or routed. See [`Request::trailers`].
 	pub body: Vec<u8>,
-	/// Parsed headers.
+	/// Parsed headers. Keys are lowercase, since header names are case-insensitive.
+	/// Use [`Request::get_header`] to look them up using any case.
+	///
+	/// If a header is repeated, only its first value is kept here.
+	/// Use [`Request::get_headers`] to get every value.
 	pub headers: HashMap<String, String>,
+	/// Values of repeated headers after the first one, in the order they were sent.
+	#[cfg_attr(feature = "json", serde(skip))]
+	repeated_headers: HashMap<String, Vec<String>>,
+	/// Trailers sent after a chunked body, with lowercase keys.
+	#[cfg_attr(feature = "json", serde(skip))]
+	trailers: Vec<(String, String)>,
+	/// Id of the connection the request was received on. See [`Request::connection_id`].
+	pub(crate) connection_id: Option<u64>,
+	/// Amount of bytes the request was parsed from. See [`Request::bytes_received`].
+	bytes_received: usize,
 }
 
 impl Request {
 	/// Parses and creates a requeset from raw text and an ip address.
 	/// Note that this does not parse the url (See [Request::url]).
 	pub fn new(bytes: &[u8], ip: SocketAddr) -> Option<Self> {
-		let mut words = bytes.split(|b| *b == b' ');
+		let method_end = bytes.iter().position(|b| *b == b' ')?;
+		let method = Method::from(&bytes[..method_end]);
 
-		let method = Method::from(words.next()?);
+		let url_start = method_end + 1;
+		let url_end = url_start + bytes[url_start..].iter().position(|b| *b == b' ')?;
+		let url = String::from_utf8(bytes[url_start..url_end].into()).ok()?;
 
-		let url_bytes = words.next()?;
-		let url = String::from_utf8(url_bytes.into()).ok()?;
-
-		words.next()?;
+		let line_end = bytes.iter().position(|b| *b == b'\n');
+		let version = bytes
+			.get(url_end + 1..line_end.unwrap_or(bytes.len()))
+			.and_then(|version| std::str::from_utf8(version).ok())
+			.map_or(HttpVersion::UNKNOWN, |version| version.trim().into());
 
 		// most browsers send 10-12 headers, and it's not that big of an allocation
 		let mut headers = HashMap::with_capacity(12);
+		let mut repeated_headers = HashMap::new();
+
+		// Headers start after the request line. The buffer is walked line by line
+		// only once, finding the start of the body at the same time.
+		let mut start = match line_end {
+			Some(position) => position + 1,
+			None => bytes.len() + 1,
+		};
+
+		let mut body_start = None;
+
+		while start <= bytes.len() {
+			let end = bytes[start..]
+				.iter()
+				.position(|b| *b == b'\n')
+				.map_or(bytes.len(), |position| start + position);
+
+			let line = &bytes[start..end];
 
-		for line in bytes.split(|b| *b == b'\n').skip(1) {
 			if line == b"\r" || line.is_empty() {
+				// `\r\n\r\n`, the usual end of the headers.
+				if line == b"\r" && end < bytes.len() && bytes[..start].ends_with(b"\r\n") {
+					body_start = Some(end + 1);
+				}
+
 				break;
 			}
 
 			let (key, value) = Self::parse_header(line)?;
-			headers.insert(key, value);
+			insert_header(&mut headers, &mut repeated_headers, key, value);
+
+			start = end + 1;
 		}
 
-		let body = if let Some(position) = bytes.windows(4).position(|window| window == b"\r\n\r\n")
-		{
-			bytes[position + 4..].into()
-		} else {
-			vec![]
+		// Headers ending in an unusual way, the body is still after the first `\r\n\r\n`.
+		let body_start = body_start.or_else(|| {
+			bytes
+				.get(start..)?
+				.windows(4)
+				.position(|window| window == b"\r\n\r\n")
+				.map(|position| start + position + 4)
+		});
+
+		let body = match body_start {
+			Some(position) => bytes[position..].into(),
+			None => vec![],
 		};
 
-		Some(Self {
+		let mut request = Self {
 			ip,
 			url,
 			method,
+			version,
 			body,
 			headers,
-		})
+			repeated_headers,
+			trailers: vec![],
+			connection_id: None,
+			bytes_received: bytes.len(),
+		};
+
+		if request
+			.get_header("Transfer-Encoding")
+			.map_or(false, is_chunked)
+		{
+			request.decode_chunked_body();
+		}
+
+		Some(request)
+	}
+
+	/// Decodes a chunked body, keeping its trailers.
+	/// The body is left as is if it's malformed or incomplete.
+	fn decode_chunked_body(&mut self) {
+		let (body, trailers) = match decode_chunked(&self.body) {
+			Ok(Some(decoded)) => decoded,
+			_ => return,
+		};
+
+		self.body = body;
+		self.headers.remove("transfer-encoding");
+		self.repeated_headers.remove("transfer-encoding");
+		self.set_header("content-length", self.body.len());
+		self.trailers = trailers;
 	}
 
 	/// A function that parses the header form the raw http request headers.
@@ -84,14 +170,59 @@ impl Request {
 		let value = &rest[1..rest.len() - 1];
 
 		Some((
-			String::from_utf8_lossy(key).trim().to_string(),
+			String::from_utf8_lossy(key).trim().to_ascii_lowercase(),
 			String::from_utf8_lossy(value).trim().to_string(),
 		))
 	}
 
-	/// Safely gets a header.
+	/// Safely gets a header. The key is case-insensitive.
+	/// If the header is repeated, the first value is returned.
 	pub fn get_header(&self, key: &str) -> Option<&str> {
-		self.headers.get(key).map(|s| s.as_str())
+		self.headers
+			.get(&key.to_ascii_lowercase())
+			.map(String::as_str)
+	}
+
+	/// Gets every value of a repeated header, in the order they were sent.
+	/// The key is case-insensitive. Returns an empty vector if the header is missing.
+	pub fn get_headers(&self, key: &str) -> Vec<&str> {
+		let key = key.to_ascii_lowercase();
+
+		self.headers
+			.get(&key)
+			.into_iter()
+			.chain(self.repeated_headers.get(&key).into_iter().flatten())
+			.map(String::as_str)
+			.collect()
+	}
+
+	/// Gets a trailer sent after a chunked body, like `Content-MD5` or a signature.
+	/// The key is case-insensitive. If the trailer is repeated, the first value is returned.
+	pub fn trailer(&self, key: &str) -> Option<&str> {
+		self.trailers
+			.iter()
+			.find(|(k, _)| k.eq_ignore_ascii_case(key))
+			.map(|(_, value)| value.as_str())
+	}
+
+	/// Gets every trailer sent after a chunked body, in order, with lowercase keys.
+	/// Empty if the body wasn't chunked or had no trailers.
+	pub fn trailers(&self) -> &[(String, String)] {
+		&self.trailers
+	}
+
+	/// Gets the id of the connection the request was received on, shared by every
+	/// request of a kept-alive connection.
+	/// Only set if enabled with [`crate::Server::with_connection_id`].
+	pub fn connection_id(&self) -> Option<u64> {
+		self.connection_id
+	}
+
+	/// Gets the amount of bytes the request was parsed from: the request line, headers and
+	/// body as received (before decoding chunked bodies). Useful for access logs,
+	/// unlike [`Request::len`], which only counts the body.
+	pub fn bytes_received(&self) -> usize {
+		self.bytes_received
 	}
 
 	/// Equivalent to `get_header(key).unwrap_or(default)`
@@ -99,14 +230,250 @@ impl Request {
 		self.get_header(key).unwrap_or(default)
 	}
 
-	/// Checks if a header exists.
+	/// Checks if a header exists. The key is case-insensitive.
 	pub fn has_header(&self, key: &str) -> bool {
-		self.headers.contains_key(key)
+		self.headers.contains_key(&key.to_ascii_lowercase())
 	}
 
-	/// Sets a header using any key and value convertible to Strings
+	/// Sets a header using any key and value convertible to Strings,
+	/// replacing every value it had. The key is stored in lowercase.
 	pub fn set_header<T: ToString, K: ToString>(&mut self, k: T, v: K) {
-		self.headers.insert(k.to_string(), v.to_string());
+		let key = k.to_string().to_ascii_lowercase();
+		self.repeated_headers.remove(&key);
+		self.headers.insert(key, v.to_string());
+	}
+
+	/// Gets the amount of headers in the request.
+	pub fn header_count(&self) -> usize {
+		self.headers.len()
+	}
+
+	/// Gets the approximate size of the request in bytes: its headers
+	/// (as `Key: value\r\n` lines) and its body. The request line is not included.
+	pub fn approx_size(&self) -> usize {
+		let headers: usize = self
+			.header_lines()
+			.map(|(key, value)| key.len() + value.len() + 4)
+			.sum();
+
+		headers + self.body.len()
+	}
+
+	/// Serializes the request back to its HTTP/1.x wire form (request line, headers
+	/// and body), e.g. to forward a (possibly modified) request to another server.
+	///
+	/// Headers are written in an arbitrary order, since they're stored in a `HashMap`,
+	/// and with lowercase names. Repeated headers are written once per value.
+	/// The body is written as is, so `Content-Length` must be updated if it's changed.
+	pub fn to_wire_bytes(&self) -> Vec<u8> {
+		let mut head = format!("{} {} {}\r\n", self.method, self.url, self.version);
+
+		for (key, value) in self.header_lines() {
+			head.push_str(&format!("{}: {}\r\n", key, value));
+		}
+
+		head.push_str("\r\n");
+
+		let mut bytes = head.into_bytes();
+		bytes.extend_from_slice(&self.body);
+		bytes
+	}
+
+	/// Iterates over every header line, including every value of repeated headers.
+	fn header_lines(&self) -> impl Iterator<Item = (&str, &str)> {
+		self.headers.iter().flat_map(move |(key, value)| {
+			let repeated = self.repeated_headers.get(key).into_iter().flatten();
+
+			std::iter::once(value)
+				.chain(repeated)
+				.map(move |value| (key.as_str(), value.as_str()))
+		})
+	}
+
+	/// Gets a single cookie from the `Cookie` header, without parsing the rest.
+	/// Surrounding whitespace and double quotes are removed from the value.
+	pub fn cookie(&self, name: &str) -> Option<&str> {
+		self.cookie_pairs()
+			.find(|(key, _)| *key == name)
+			.map(|(_, value)| value)
+	}
+
+	/// Parses all the cookies in the `Cookie` header into a map.
+	/// Values are trimmed like in [`Request::cookie`], but not percent-decoded.
+	/// If a cookie is repeated, only the first value is kept.
+	///
+	/// Returns an empty map if there's no `Cookie` header.
+	pub fn cookies(&self) -> HashMap<String, String> {
+		let mut cookies = HashMap::new();
+
+		for (key, value) in self.cookie_pairs() {
+			cookies
+				.entry(key.to_string())
+				.or_insert_with(|| value.to_string());
+		}
+
+		cookies
+	}
+
+	/// Iterates over the `name=value` pairs in the `Cookie` header, trimmed.
+	fn cookie_pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+		self.get_headers("Cookie")
+			.into_iter()
+			.flat_map(|cookies| cookies.split(';'))
+			.filter_map(|pair| pair.split_once('='))
+			.map(|(key, value)| {
+				let value = value.trim();
+				let value = value
+					.strip_prefix('"')
+					.and_then(|v| v.strip_suffix('"'))
+					.unwrap_or(value);
+
+				(key.trim(), value)
+			})
+	}
+
+	/// Gets the method a `POST` request wants to be treated as, either from the
+	/// `X-HTTP-Method-Override` header or a `_method` form field (in that order).
+	/// Returns `None` for other methods or if no valid override is found.
+	pub fn method_override(&self) -> Option<Method> {
+		if self.method != Method::POST {
+			return None;
+		}
+
+		let value = match self.get_header("X-HTTP-Method-Override") {
+			Some(header) => header.to_string(),
+			None => {
+				let content_type = self.get_header("Content-Type")?;
+				if !content_type.starts_with("application/x-www-form-urlencoded") {
+					return None;
+				}
+
+				self.form().remove("_method")?
+			}
+		};
+
+		match Method::from(value.trim().to_ascii_uppercase().as_bytes()) {
+			Method::UNKNOWN => None,
+			method => Some(method),
+		}
+	}
+
+	/// Checks if the client wants to keep the connection open, using the `Connection`
+	/// header (`keep-alive` or `close`) or, if it doesn't say, the HTTP version:
+	/// HTTP/1.1 connections are kept open by default, and HTTP/1.0 ones aren't.
+	pub fn wants_keep_alive(&self) -> bool {
+		let options = self.get_header_or("Connection", "");
+
+		for option in options.split(',').map(str::trim) {
+			if option.eq_ignore_ascii_case("close") {
+				return false;
+			}
+
+			if option.eq_ignore_ascii_case("keep-alive") {
+				return true;
+			}
+		}
+
+		matches!(
+			self.version,
+			HttpVersion::V1_1 | HttpVersion::V2_0 | HttpVersion::V3_0
+		)
+	}
+
+	/// Gets the `Origin` header of the request.
+	pub fn origin(&self) -> Option<&str> {
+		self.get_header("Origin")
+	}
+
+	/// Checks if the request comes from `expected_host` (like `example.com:8080`),
+	/// using the `Origin` header or, if missing, the `Referer` header.
+	/// Useful against CSRF in state-changing requests.
+	///
+	/// Returns `false` if neither header is present.
+	pub fn is_same_origin(&self, expected_host: &str) -> bool {
+		let source = match self.origin().or_else(|| self.get_header("Referer")) {
+			Some(source) => source,
+			None => return false,
+		};
+
+		let without_scheme = match source.split_once("://") {
+			Some((_, rest)) => rest,
+			None => return false,
+		};
+
+		let host = without_scheme.split('/').next().unwrap_or("");
+		host.eq_ignore_ascii_case(expected_host)
+	}
+
+	/// Checks if the request is a CORS preflight request: an `OPTIONS` request
+	/// with the `Access-Control-Request-Method` header.
+	pub fn is_preflight(&self) -> bool {
+		self.method == Method::OPTIONS && self.has_header("Access-Control-Request-Method")
+	}
+
+	/// Picks the media type the client prefers among `offered`, using the `Accept`
+	/// header and its quality values. Types the client values equally are picked in
+	/// the order of `offered`, and any offered type is acceptable without `Accept`.
+	///
+	/// Returns `None` if the client accepts none of them.
+	pub fn accept_best<'a>(&self, offered: &[&'a str]) -> Option<&'a str> {
+		let accept = match self.get_header("Accept") {
+			Some(accept) => accept,
+			None => return offered.first().copied(),
+		};
+
+		let mut best: Option<(&'a str, f32)> = None;
+
+		for media_type in offered {
+			let quality = accept_quality(accept, media_type);
+
+			match best {
+				Some((_, best_quality)) if best_quality >= quality => {}
+				_ if quality > 0.0 => best = Some((media_type, quality)),
+				_ => {}
+			}
+		}
+
+		best.map(|(media_type, _)| media_type)
+	}
+
+	/// Gets the byte range of the `Range` header, if it has a single valid one.
+	/// See [`crate::Response::with_range`].
+	pub fn range(&self) -> Option<crate::Range> {
+		self.get_header("Range").and_then(crate::Range::parse)
+	}
+
+	/// Gets the entity tags of the `If-Match` header, as sent (e.g. `"v1"` or `*`).
+	pub fn if_match(&self) -> Option<Vec<&str>> {
+		let value = self.get_header("If-Match")?;
+		Some(
+			value
+				.split(',')
+				.map(str::trim)
+				.filter(|t| !t.is_empty())
+				.collect(),
+		)
+	}
+
+	/// Checks the `If-Match` precondition against the current (strong) `ETag` of the
+	/// resource, given like in `Response::with_etag` (without quotes).
+	///
+	/// Returns a `412 Precondition Failed` response if no tag matches. Weak tags
+	/// never match, and `*` matches any existing resource. Requests without
+	/// `If-Match` always pass.
+	pub fn check_if_match(&self, current_etag: &str) -> Result<(), crate::Response> {
+		let tags = match self.if_match() {
+			Some(tags) => tags,
+			None => return Ok(()),
+		};
+
+		let current = format!("\"{}\"", current_etag);
+
+		if tags.iter().any(|tag| *tag == "*" || *tag == current) {
+			Ok(())
+		} else {
+			Err(crate::response!(precondition_failed))
+		}
 	}
 
 	/// Gets the length of the body.
@@ -169,10 +536,142 @@ impl Request {
 		self.json().map_err(|e| e.to_response())
 	}
 
+	/// Get the body as a JSON value, or `T::default()` if it's empty or invalid.
+	#[cfg(feature = "json")]
+	pub fn json_or<T>(&self) -> T
+	where
+		T: Default + for<'a> serde::de::Deserialize<'a>,
+	{
+		self.json().unwrap_or_default()
+	}
+
+	/// Get the body parsed according to its `Content-Type`: JSON (`application/json`)
+	/// or a form (`application/x-www-form-urlencoded`).
+	///
+	/// Parse errors are converted to a bad request response, and other content types
+	/// to an unsupported media type response, so the `?` operator can be used.
+	#[cfg(feature = "json")]
+	pub fn body_typed<T>(&self) -> Result<T, crate::Response>
+	where
+		T: for<'a> serde::de::Deserialize<'a>,
+	{
+		let content_type = self.get_header_or("Content-Type", "");
+		let mime = content_type.split(';').next().unwrap_or("").trim();
+
+		if mime.eq_ignore_ascii_case("application/json") {
+			self.force_json()
+		} else if mime.eq_ignore_ascii_case("application/x-www-form-urlencoded") {
+			serde_urlencoded::from_bytes(&self.body).map_err(|e| e.to_response())
+		} else {
+			Err(crate::response!(unsupported_media_type))
+		}
+	}
+
+	/// Get the body as newline-delimited JSON (NDJSON), parsing each line lazily.
+	/// Blank lines (including a trailing newline) are skipped.
+	#[cfg(feature = "json")]
+	pub fn json_lines<'a, T>(&'a self) -> impl Iterator<Item = serde_json::Result<T>> + 'a
+	where
+		T: for<'de> serde::de::Deserialize<'de> + 'a,
+	{
+		self.body
+			.split(|b| *b == b'\n')
+			.filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+			.map(serde_json::from_slice)
+	}
+
+	/// Parses the body as an `application/x-www-form-urlencoded` form,
+	/// percent-decoding keys and values (`+` is decoded as a space).
+	/// If a key is repeated, only the last value is kept.
+	pub fn form(&self) -> HashMap<String, String> {
+		self.text()
+			.split('&')
+			.filter(|pair| !pair.is_empty())
+			.map(|pair| {
+				let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+
+				(
+					percent_decode(key, true).into_owned(),
+					percent_decode(value, true).into_owned(),
+				)
+			})
+			.collect()
+	}
+
+	/// Merges the search params of the URL with the parsed body, a form
+	/// (`application/x-www-form-urlencoded`) or, with the `json` feature, a JSON
+	/// object (`application/json`). Body values override search params with the same key.
+	///
+	/// Keys and values are percent-decoded, except for JSON bodies. JSON strings are
+	/// used as is, and other JSON values (numbers, nested objects...) as JSON text.
+	/// Bodies that can't be parsed are ignored.
+	pub fn params(&self) -> HashMap<String, String> {
+		let mut params: HashMap<String, String> = self
+			.parse_url()
+			.search_params
+			.iter()
+			.map(|(key, value)| {
+				(
+					percent_decode(key, true).into_owned(),
+					percent_decode(value, true).into_owned(),
+				)
+			})
+			.collect();
+
+		let content_type = self.get_header_or("Content-Type", "");
+		let mime = content_type.split(';').next().unwrap_or("").trim();
+
+		if mime.eq_ignore_ascii_case("application/x-www-form-urlencoded") {
+			params.extend(self.form());
+		}
+
+		#[cfg(feature = "json")]
+		if mime.eq_ignore_ascii_case("application/json") {
+			if let Ok(serde_json::Value::Object(object)) = self.json() {
+				params.extend(object.into_iter().map(|(key, value)| match value {
+					serde_json::Value::String(text) => (key, text),
+					value => (key, value.to_string()),
+				}));
+			}
+		}
+
+		params
+	}
+
+	/// Parses the body as `multipart/form-data`, usually used for file uploads.
+	/// The contents of each part are kept untouched. See [`Part`].
+	///
+	/// Returns `None` if the request isn't `multipart/form-data`, or the
+	/// boundary or body are malformed.
+	pub fn multipart(&self) -> Option<Vec<Part>> {
+		let boundary = crate::multipart::boundary(self.get_header("Content-Type")?)?;
+		crate::multipart::parse(&self.body, boundary)
+	}
+
 	/// Get a parsed version of the URL.
+	/// The scheme and host of absolute-form targets (`http://host/path`) are skipped.
 	/// See [Url]
 	pub fn parse_url(&self) -> Url<'_> {
-		self.url.as_str().into()
+		match split_absolute_form(&self.url) {
+			Some((_, path)) => path.into(),
+			None => self.url.as_str().into(),
+		}
+	}
+
+	/// Gets the host of the request (possibly with a port): the host of an
+	/// absolute-form target (`GET http://example.com/ HTTP/1.1`), or the `Host` header.
+	/// Internationalized hosts are returned as sent (in punycode).
+	pub fn host(&self) -> Option<&str> {
+		match split_absolute_form(&self.url) {
+			Some((host, _)) => Some(host),
+			None => self.get_header("Host"),
+		}
+	}
+
+	/// Get a parsed version of the URL that doesn't borrow the request.
+	/// See [OwnedUrl]
+	pub fn parse_url_owned(&self) -> OwnedUrl {
+		self.parse_url().into()
 	}
 
 	/// Get the IP address of the client, formatted.
@@ -180,3 +679,164 @@ impl Request {
 		crate::util::format_addr(self.ip)
 	}
 }
+
+/// Splits an absolute-form target (`scheme://host/path?query`) into its host
+/// and the rest, skipping user information. Returns `None` for other targets.
+fn split_absolute_form(url: &str) -> Option<(&str, &str)> {
+	let (scheme, rest) = url.split_once("://")?;
+
+	if scheme.is_empty()
+		|| !scheme
+			.bytes()
+			.all(|b| b.is_ascii_alphanumeric() || b"+-.".contains(&b))
+	{
+		return None;
+	}
+
+	let end = rest.find(['/', '?']).unwrap_or(rest.len());
+	let authority = &rest[..end];
+	let host = authority
+		.rsplit_once('@')
+		.map_or(authority, |(_, host)| host);
+
+	Some((host, &rest[end..]))
+}
+
+/// Gets the quality value an `Accept` header value gives to `media_type`, using the
+/// most specific matching range (`type/subtype`, then `type/*`, then `*/*`).
+/// Types that don't match any range get `0`.
+fn accept_quality(accept: &str, media_type: &str) -> f32 {
+	let (main_type, _) = media_type.split_once('/').unwrap_or((media_type, ""));
+
+	// (specificity, quality) of the best match so far.
+	let mut best: Option<(u8, f32)> = None;
+
+	for item in accept.split(',') {
+		let mut parts = item.split(';');
+		let range = parts.next().unwrap_or("").trim();
+
+		let quality = parts
+			.filter_map(|param| param.trim().strip_prefix("q="))
+			.find_map(|q| q.trim().parse::<f32>().ok())
+			.unwrap_or(1.0);
+
+		let specificity = if range.eq_ignore_ascii_case(media_type) {
+			3
+		} else if range
+			.strip_suffix("/*")
+			.map_or(false, |r| r.eq_ignore_ascii_case(main_type))
+		{
+			2
+		} else if range == "*/*" {
+			1
+		} else {
+			continue;
+		};
+
+		if best.map_or(true, |(s, _)| specificity > s) {
+			best = Some((specificity, quality));
+		}
+	}
+
+	best.map_or(0.0, |(_, quality)| quality)
+}
+
+/// Adds a parsed header. Values of repeated headers after the first one
+/// are kept apart, see [`Request::get_headers`].
+fn insert_header(
+	headers: &mut HashMap<String, String>,
+	repeated_headers: &mut HashMap<String, Vec<String>>,
+	key: String,
+	value: String,
+) {
+	match headers.entry(key) {
+		Entry::Occupied(entry) => repeated_headers
+			.entry(entry.key().clone())
+			.or_default()
+			.push(value),
+		Entry::Vacant(entry) => {
+			entry.insert(value);
+		}
+	}
+}
+
+/// Checks if a `Transfer-Encoding` header value ends with the chunked encoding.
+pub(crate) fn is_chunked(transfer_encoding: &str) -> bool {
+	transfer_encoding
+		.rsplit(',')
+		.next()
+		.map_or(false, |coding| {
+			coding.trim().eq_ignore_ascii_case("chunked")
+		})
+}
+
+/// A decoded chunked body: the data and the trailers.
+type ChunkedBody = (Vec<u8>, Vec<(String, String)>);
+
+/// A chunked body that can't be decoded, no matter what's sent after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MalformedChunks;
+
+/// Decodes a chunked body. Returns `Ok(None)` if it's incomplete.
+pub(crate) fn decode_chunked(bytes: &[u8]) -> Result<Option<ChunkedBody>, MalformedChunks> {
+	let line_end = |from: usize| {
+		bytes
+			.get(from..)?
+			.windows(2)
+			.position(|window| window == b"\r\n")
+			.map(|position| from + position)
+	};
+
+	let mut data = vec![];
+	let mut position = 0;
+
+	loop {
+		let end = match line_end(position) {
+			Some(end) => end,
+			None => return Ok(None),
+		};
+
+		// Chunk extensions (`;name=value`) are ignored.
+		let size = std::str::from_utf8(&bytes[position..end])
+			.ok()
+			.and_then(|line| line.split(';').next())
+			.and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+			.ok_or(MalformedChunks)?;
+		position = end + 2;
+
+		if size == 0 {
+			break;
+		}
+
+		let chunk_end = position.checked_add(size).ok_or(MalformedChunks)?;
+		let next = chunk_end.checked_add(2).ok_or(MalformedChunks)?;
+
+		match bytes.get(chunk_end..next) {
+			Some(b"\r\n") => {}
+			Some(_) => return Err(MalformedChunks),
+			None => return Ok(None),
+		}
+
+		data.extend_from_slice(&bytes[position..chunk_end]);
+		position = next;
+	}
+
+	// Trailers, ended by an empty line.
+	let mut trailers = vec![];
+
+	loop {
+		let end = match line_end(position) {
+			Some(end) => end,
+			None => return Ok(None),
+		};
+
+		if end == position {
+			return Ok(Some((data, trailers)));
+		}
+
+		// The line is passed with its `\r`, like request headers.
+		let trailer = Request::parse_header(&bytes[position..=end]).ok_or(MalformedChunks)?;
+		trailers.push(trailer);
+		position = end + 2;
+	}
+}
@@ -0,0 +1,62 @@
+//! A module that provides a builder for responses with any status code.
+
+use super::{response_types::reason_phrase, Headers, Response};
+use crate::HttpVersion;
+
+/// Builds a [`Response`] step by step, looking up the status text from the status code.
+/// Created with [`Response::builder`].
+///
+/// # Example
+/// ```rust
+/// use snowboard::Response;
+///
+/// let response = Response::builder()
+///     .status(418)
+///     .header("X-Tea", "Earl Grey")
+///     .body("Can't brew coffee")
+///     .build();
+///
+/// assert_eq!(response.status_text, "I'm a teapot");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ResponseBuilder {
+	/// The response being built.
+	response: Response,
+}
+
+impl ResponseBuilder {
+	/// Sets the status code, along with its reason phrase (see [`crate::reason_phrase`]).
+	/// Defaults to `200 Ok`.
+	pub fn status(mut self, status: u16) -> Self {
+		self.response.status = status;
+		self.response.status_text = reason_phrase(status);
+		self
+	}
+
+	/// Sets a header, replacing it if it was already set.
+	pub fn header(mut self, key: &'static str, value: impl ToString) -> Self {
+		self.response
+			.headers
+			.get_or_insert_with(Headers::new)
+			.insert(key, value.to_string());
+
+		self
+	}
+
+	/// Sets the body. Defaults to an empty one.
+	pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+		self.response.bytes = body.into();
+		self
+	}
+
+	/// Sets the HTTP version. Defaults to [`crate::DEFAULT_HTTP_VERSION`].
+	pub fn version(mut self, version: HttpVersion) -> Self {
+		self.response.version = version;
+		self
+	}
+
+	/// Finishes building the response.
+	pub fn build(self) -> Response {
+		self.response
+	}
+}
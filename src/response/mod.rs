@@ -1,14 +1,32 @@
 //! A module that provides code and other modules to serialize/deserialize response into appropriate
 //! data types.
 
+mod builder;
 mod response_types;
 mod responselike;
 
+pub use builder::ResponseBuilder;
+pub use response_types::reason_phrase;
+#[cfg(feature = "json")]
+pub use responselike::Json;
 pub use responselike::ResponseLike;
 
-use std::{collections::HashMap, fmt, io};
+use std::{
+	collections::HashMap,
+	fmt,
+	fs::File,
+	io::{self, Read, Seek, SeekFrom, Write},
+	path::Path,
+	sync::{Arc, Mutex},
+};
 
-use crate::HttpVersion;
+use crate::{
+	mime_from_extension, Cookie, HttpVersion, Method, Range, Request, StreamExt, Url,
+	DEFAULT_BUFFER_SIZE,
+};
+
+/// Maximum amount of body bytes shown by [`Response::to_debug_string`].
+const DEBUG_BODY_PREVIEW: usize = 256;
 
 /// The default HTTP version used by the server.
 pub const DEFAULT_HTTP_VERSION: HttpVersion = HttpVersion::V1_1;
@@ -18,9 +36,9 @@ pub const DEFAULT_HTTP_VERSION: HttpVersion = HttpVersion::V1_1;
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Response {
 	/// HTTP protocol version.
-	/// Do note the server only supports HTTP/1.1, so even if
+	/// Do note the server only supports HTTP/1.0 and HTTP/1.1, so even if
 	/// this is specified as HTTP/2.0 or any other, it'll still
-	/// be a HTTP/1.1 response.
+	/// be sent as a HTTP/1.1 response. See [`Response::wire_version`].
 	pub version: HttpVersion,
 	/// HTTP status code.
 	pub status: u16,
@@ -30,9 +48,96 @@ pub struct Response {
 	pub bytes: Vec<u8>,
 	/// Headers of the response
 	pub headers: Option<Headers>,
+	/// Length of the body before it was compressed, if it was.
+	original_len: Option<usize>,
+	/// Source of a streamed body, sent instead of `bytes`. See [`Response::from_reader`].
+	/// Boxed so responses stay small, since they're often returned as errors.
+	reader: Option<Box<BodyReader>>,
+}
+
+/// Formats events as Server-Sent Events frames. See [`Response::event_stream`].
+struct EventReader<I> {
+	/// Events left to send.
+	events: I,
+	/// The frame of the last event.
+	frame: Vec<u8>,
+	/// Amount of bytes of the frame already read.
+	sent: usize,
+}
+
+impl<I: Iterator<Item = (String, String)>> Read for EventReader<I> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if self.sent == self.frame.len() {
+			let (event, data) = match self.events.next() {
+				Some(next) => next,
+				None => return Ok(0),
+			};
+
+			self.frame.clear();
+			self.sent = 0;
+
+			if !event.is_empty() {
+				writeln!(self.frame, "event: {}", event)?;
+			}
+
+			// Every line needs its own field, or the data would end early.
+			for line in data.split('\n') {
+				writeln!(self.frame, "data: {}", line)?;
+			}
+
+			self.frame.push(b'\n');
+		}
+
+		let read = (&self.frame[self.sent..]).read(buf)?;
+		self.sent += read;
+
+		Ok(read)
+	}
+}
+
+/// The body of a response, as split by [`Response::into_parts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Body {
+	/// A body kept in memory.
+	Bytes(Vec<u8>),
+	/// A compressed body kept in memory, along with its length before it was compressed.
+	/// See [`Response::original_len`].
+	Compressed(Vec<u8>, usize),
+	/// A streamed body. See [`Response::from_reader`].
+	Stream(BodyReader),
+}
+
+/// The source of a streamed response body.
+/// Clones share the same reader, and are only equal to each other.
+#[derive(Clone)]
+pub struct BodyReader {
+	/// The shared reader.
+	reader: Arc<Mutex<Box<dyn Read + Send>>>,
+	/// Maximum amount of bytes read per chunk.
+	chunk_size: usize,
+}
+
+impl fmt::Debug for BodyReader {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("BodyReader")
+			.field("chunk_size", &self.chunk_size)
+			.finish_non_exhaustive()
+	}
 }
 
+impl PartialEq for BodyReader {
+	fn eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.reader, &other.reader) && self.chunk_size == other.chunk_size
+	}
+}
+
+impl Eq for BodyReader {}
+
 /// Equivalent to `HashMap<&'static str, String>`.
+///
+/// Since keys can't be repeated, headers sent more than once (like `Set-Cookie`)
+/// are stored as a single value with one line per header, and sent as separate
+/// headers. See [`Response::add_cookie`].
 pub type Headers = HashMap<&'static str, String>;
 
 impl Response {
@@ -51,17 +156,196 @@ impl Response {
 			status_text,
 			bytes,
 			headers,
+			original_len: None,
+			reader: None,
+		}
+	}
+
+	/// Creates a [`ResponseBuilder`], which looks up the status text from the status code.
+	/// Useful for status codes without their own constructor.
+	pub fn builder() -> ResponseBuilder {
+		ResponseBuilder::default()
+	}
+
+	/// Creates a response whose body is streamed from `reader` instead of being kept
+	/// in memory, like a big file. It's sent with `Transfer-Encoding: chunked`, reading
+	/// up to [`DEFAULT_BUFFER_SIZE`] bytes per chunk (see [`Response::with_chunk_size`]).
+	///
+	/// Streamed bodies can only be sent once, and aren't included in [`Response::to_bytes`],
+	/// [`fmt::Display`] or compressed. A reading error stops the response midway,
+	/// so the client can notice it's incomplete.
+	///
+	/// # Example
+	/// ```rust
+	/// use snowboard::Response;
+	/// use std::fs::File;
+	///
+	/// fn video() -> std::io::Result<Response> {
+	///     let file = File::open("video.mp4")?;
+	///     Ok(Response::from_reader(200, file).with_content_type("video/mp4".into()))
+	/// }
+	/// ```
+	pub fn from_reader(status: u16, reader: impl Read + Send + 'static) -> Self {
+		let mut response = Self::new(
+			DEFAULT_HTTP_VERSION,
+			status,
+			response_types::reason_phrase(status),
+			vec![],
+			Some(crate::headers! { "Transfer-Encoding" => "chunked" }),
+		);
+
+		response.reader = Some(Box::new(BodyReader {
+			reader: Arc::new(Mutex::new(Box::new(reader))),
+			chunk_size: DEFAULT_BUFFER_SIZE,
+		}));
+
+		response
+	}
+
+	/// Creates a `text/event-stream` response (Server-Sent Events), streaming every
+	/// `(event, data)` pair as soon as it's received, until there are no more.
+	/// Empty event names are left out, which browsers handle as `message` events.
+	///
+	/// # Example
+	/// ```rust
+	/// use snowboard::Response;
+	/// use std::{sync::mpsc, thread};
+	///
+	/// fn events() -> Response {
+	///     let (sender, receiver) = mpsc::channel();
+	///
+	///     thread::spawn(move || {
+	///         for i in 0..3 {
+	///             let _ = sender.send(("tick".into(), i.to_string()));
+	///         }
+	///     });
+	///
+	///     Response::event_stream(receiver)
+	/// }
+	/// ```
+	pub fn event_stream<I>(events: I) -> Self
+	where
+		I: IntoIterator<Item = (String, String)>,
+		I::IntoIter: Send + 'static,
+	{
+		let reader = EventReader {
+			events: events.into_iter(),
+			frame: vec![],
+			sent: 0,
+		};
+
+		Self::from_reader(200, reader)
+			.with_header("Content-Type", "text/event-stream".into())
+			.with_header("Cache-Control", "no-cache".into())
+	}
+
+	/// Sets how many bytes (at least 1) are read from the source of a streamed body
+	/// per chunk sent, returning the response itself. Smaller chunks reach the client
+	/// sooner, while bigger ones need fewer writes. Does nothing if the body isn't streamed.
+	pub fn with_chunk_size(mut self, size: usize) -> Self {
+		if let Some(reader) = &mut self.reader {
+			reader.chunk_size = size.max(1);
+		}
+
+		self
+	}
+
+	/// Checks if the body is streamed. See [`Response::from_reader`].
+	pub fn is_streamed(&self) -> bool {
+		self.reader.is_some()
+	}
+
+	/// Splits the response into its status, status text, headers and body.
+	/// The HTTP version is dropped, see [`Response::from_parts`].
+	pub fn into_parts(self) -> (u16, &'static str, Option<Headers>, Body) {
+		let body = match (self.reader, self.original_len) {
+			(Some(reader), _) => Body::Stream(*reader),
+			(None, Some(original_len)) => Body::Compressed(self.bytes, original_len),
+			(None, None) => Body::Bytes(self.bytes),
+		};
+
+		(self.status, self.status_text, self.headers, body)
+	}
+
+	/// Creates a response from the parts returned by [`Response::into_parts`],
+	/// using the default HTTP version.
+	pub fn from_parts(
+		(status, status_text, headers, body): (u16, &'static str, Option<Headers>, Body),
+	) -> Self {
+		let mut response = Self::new(DEFAULT_HTTP_VERSION, status, status_text, vec![], headers);
+
+		match body {
+			Body::Bytes(bytes) => response.bytes = bytes,
+			Body::Compressed(bytes, original_len) => {
+				response.bytes = bytes;
+				response.original_len = Some(original_len);
+			}
+			Body::Stream(reader) => response.reader = Some(Box::new(reader)),
 		}
+
+		response
 	}
 
 	/// Writes the response, consuming its body.
 	pub fn send_to<T: io::Write>(&mut self, stream: &mut T) -> Result<(), io::Error> {
-		let prev = self.prepare_response().into_bytes();
+		let prev = self.prepare_wire_response().into_bytes();
 		stream.write_all(&prev)?;
+
+		if let Some(reader) = self.reader.take() {
+			return write_chunked(&reader, stream);
+		}
+
 		stream.write_all(&self.bytes)?;
 		stream.flush()
 	}
 
+	/// Sends the response to a connection stream, like [`Response::send_to`], but
+	/// writing the head and the body at once, which saves a syscall (or a TLS record).
+	pub fn send_to_stream<T: StreamExt>(&mut self, stream: &mut T) -> Result<(), io::Error> {
+		if self.is_streamed() {
+			return self.send_to(stream);
+		}
+
+		let head = self.prepare_wire_response().into_bytes();
+		stream.write_coalesced(&[&head, &self.bytes])?;
+		stream.flush()
+	}
+
+	/// Creates a JSON error response with the given status, with the body
+	/// `{"error":{"code":<status>,"message":"..."}}`.
+	#[cfg(feature = "json")]
+	pub fn json_error(status: u16, message: &str) -> Self {
+		let body = serde_json::json!({
+			"error": {
+				"code": status,
+				"message": message,
+			}
+		});
+
+		Self::new(
+			DEFAULT_HTTP_VERSION,
+			status,
+			response_types::reason_phrase(status),
+			body.to_string().into_bytes(),
+			Some(crate::headers! {
+				"Content-Type" => "application/json; charset=utf-8",
+			}),
+		)
+	}
+
+	/// Writes a `103 Early Hints` interim response with the given `Link` values.
+	/// Can be called any number of times before sending the final response
+	/// on the same stream, e.g. to `rel=preload` assets. Handlers don't get the
+	/// stream, so use [`crate::Server::with_early_hints`] to send them from a server.
+	pub fn send_early_hints<T: io::Write>(stream: &mut T, links: &[&str]) -> Result<(), io::Error> {
+		crate::response!(
+			early_hints,
+			[],
+			crate::headers! { "Link" => links.join(", ") }
+		)
+		.send_to(stream)
+	}
+
 	/// Sets a header to the response, returning the response itself.
 	/// Use Response::with_content_type for the 'Content-Type' header.
 	pub fn with_header(mut self, key: &'static str, value: String) -> Self {
@@ -72,12 +356,350 @@ impl Response {
 		self
 	}
 
+	/// Sets the HTTP version of the response, returning the response itself.
+	///
+	/// The server only speaks HTTP/1.0 and HTTP/1.1, so other versions (like
+	/// [`HttpVersion::V2_0`]) are silently sent as HTTP/1.1. See [`Response::wire_version`].
+	pub fn with_version(mut self, version: HttpVersion) -> Self {
+		self.version = version;
+		self
+	}
+
+	/// Gets the HTTP version used in the status line when the response is sent.
+	/// The server can only speak HTTP/1.0 and HTTP/1.1, so other versions become HTTP/1.1.
+	pub fn wire_version(&self) -> HttpVersion {
+		match self.version {
+			HttpVersion::V1_0 => HttpVersion::V1_0,
+			_ => HttpVersion::V1_1,
+		}
+	}
+
 	/// Sets the content type of the response, returning the response itself.
 	/// Note that this does not check if the content type is valid, so be careful.
 	pub fn with_content_type(self, value: String) -> Self {
 		self.with_header("Content-Type", value)
 	}
 
+	/// Sets a cookie with no attributes, returning the response itself.
+	/// Use [`Response::add_cookie`] for cookies with attributes.
+	pub fn with_cookie(mut self, name: &str, value: &str) -> Self {
+		self.add_cookie(&Cookie::new(name, value));
+		self
+	}
+
+	/// Adds a `Set-Cookie` header, keeping the cookies already set.
+	pub fn add_cookie(&mut self, cookie: &Cookie) -> &mut Self {
+		let value = match self.headers.as_ref().and_then(|h| h.get("Set-Cookie")) {
+			Some(cookies) => format!("{}\n{}", cookies, cookie),
+			None => cookie.to_string(),
+		};
+
+		self.set_header("Set-Cookie", value)
+	}
+
+	/// Compresses the body using the encoding the client prefers, according to its
+	/// `Accept-Encoding` header, among the ones in `priority`. See [`crate::Encoding`].
+	///
+	/// The response is left untouched if it's empty, already encoded, or no
+	/// encoding is accepted. `Vary: Accept-Encoding` is added otherwise.
+	#[cfg(feature = "compression")]
+	pub fn compress(mut self, accept_encoding: &str, priority: &[crate::Encoding]) -> Self {
+		let already_encoded = self
+			.headers
+			.as_ref()
+			.map_or(false, |h| h.contains_key("Content-Encoding"));
+
+		if self.is_empty() || self.is_streamed() || already_encoded {
+			return self;
+		}
+
+		let encoding = match crate::Encoding::negotiate(accept_encoding, priority) {
+			Some(encoding) => encoding,
+			None => return self,
+		};
+
+		// Sending the body uncompressed is better than failing.
+		if let Ok(bytes) = encoding.encode(&self.bytes) {
+			let vary = match self.headers.as_ref().and_then(|h| h.get("Vary")) {
+				Some(vary) => format!("{}, Accept-Encoding", vary),
+				None => "Accept-Encoding".into(),
+			};
+
+			let has_length = self
+				.headers
+				.as_ref()
+				.map_or(false, |h| h.contains_key("Content-Length"));
+
+			self.original_len = Some(self.original_len());
+			self.bytes = bytes;
+			self.set_header("Content-Encoding", encoding.as_str().into())
+				.set_header("Vary", vary);
+
+			// A length set before compressing would be wrong now.
+			if has_length {
+				self.set_content_length(self.bytes.len());
+			}
+		}
+
+		self
+	}
+
+	/// Compresses the body using gzip, whatever the client accepts, setting
+	/// `Content-Encoding` and `Content-Length`. Useful for storing pre-compressed
+	/// responses. Fails if the body is streamed or already encoded.
+	#[cfg(feature = "compression")]
+	pub fn compress_gzip(mut self) -> io::Result<Self> {
+		let already_encoded = self
+			.headers
+			.as_ref()
+			.map_or(false, |h| h.contains_key("Content-Encoding"));
+
+		if self.is_streamed() || already_encoded {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"the body is streamed or already encoded",
+			));
+		}
+
+		let bytes = crate::Encoding::Gzip.encode(&self.bytes)?;
+
+		self.original_len = Some(self.original_len());
+		self.set_header("Content-Encoding", "gzip".into())
+			.set_content_length(bytes.len());
+		self.bytes = bytes;
+
+		Ok(self)
+	}
+
+	/// Creates a `303 See Other` redirect to `url`, the usual response for
+	/// POST-redirect-GET. The `Location` is percent-encoded, see [`Url::to_encoded_string`].
+	pub fn redirect_to(url: &Url<'_>) -> Self {
+		crate::response!(
+			see_other,
+			[],
+			crate::headers! { "Location" => url.to_encoded_string() }
+		)
+	}
+
+	/// Creates an empty `200 Ok` response with an `X-Accel-Redirect` header, making nginx
+	/// serve `internal_path` instead (usually a file, after checking permissions in the handler).
+	///
+	/// nginx only follows it to locations marked as `internal`, like:
+	/// ```text
+	/// location /protected/ {
+	///     internal;
+	///     alias /var/www/files/;
+	/// }
+	/// ```
+	pub fn accel_redirect(internal_path: &str) -> Self {
+		crate::response!(
+			ok,
+			[],
+			crate::headers! { "X-Accel-Redirect" => internal_path }
+		)
+	}
+
+	/// Creates a `200 Ok` response with the contents of a file, setting its `Content-Length`
+	/// and its `Content-Type` according to the extension (`application/octet-stream`
+	/// if it's unknown).
+	///
+	/// Returns an error if the file can't be read. When returned from a handler,
+	/// missing files become a `404 Not Found` (see [`ResponseLike`] for `io::Result`).
+	/// Use [`Response::with_range`] to support `Range` requests.
+	///
+	/// # Example
+	/// ```rust
+	/// use snowboard::{Response, Server};
+	///
+	/// let server = Server::new("localhost:8080").expect("failed to start server");
+	/// // server.run(|_| Response::file("static/index.html"));
+	/// ```
+	pub fn file(path: impl AsRef<Path>) -> io::Result<Self> {
+		let path = path.as_ref();
+		let bytes = std::fs::read(path)?;
+		let content_type = path
+			.extension()
+			.and_then(|ext| ext.to_str())
+			.map_or("application/octet-stream", mime_from_extension);
+
+		let mut response = crate::response!(
+			ok,
+			bytes,
+			crate::headers! {
+				"Content-Type" => content_type,
+				"Accept-Ranges" => "bytes",
+			}
+		);
+
+		response.set_content_length(response.len());
+		Ok(response)
+	}
+
+	/// Sets the `Allow` header to the given methods (like `GET, POST`), in order and
+	/// without duplicates, returning the response itself. Usually used with
+	/// `405 Method Not Allowed` responses and `OPTIONS` requests.
+	pub fn with_allow(self, methods: &[Method]) -> Self {
+		let mut names: Vec<&str> = vec![];
+
+		for method in methods {
+			if !names.contains(&method.as_str()) {
+				names.push(method.as_str());
+			}
+		}
+
+		self.with_header("Allow", names.join(", "))
+	}
+
+	/// Sets the `Content-Range` header to `bytes start-end/total`, returning the response itself.
+	/// Note that `end` is inclusive. Usually used with `206 Partial Content` responses.
+	pub fn with_content_range(self, start: u64, end: u64, total: u64) -> Self {
+		self.with_header(
+			"Content-Range",
+			format!("bytes {}-{}/{}", start, end, total),
+		)
+	}
+
+	/// Creates a `416 Range Not Satisfiable` response for a resource of `total` bytes.
+	/// (`Response::range_not_satisfiable` is the usual status constructor)
+	pub fn unsatisfiable_range(total: u64) -> Self {
+		crate::response!(
+			range_not_satisfiable,
+			[],
+			crate::headers! { "Content-Range" => format!("bytes */{}", total) }
+		)
+	}
+
+	/// Answers the `Range` header of a `GET` request with part of the body, so media
+	/// players can seek through it. `Accept-Ranges: bytes` is set on `200 Ok` responses.
+	///
+	/// A single satisfiable range becomes a `206 Partial Content` with `Content-Range`,
+	/// and one out of bounds a `416 Range Not Satisfiable`. Requests with several
+	/// ranges get the whole body. Streamed and encoded bodies are left untouched.
+	///
+	/// # Example
+	/// ```rust
+	/// use snowboard::{Request, Response};
+	///
+	/// fn video(req: Request) -> std::io::Result<Response> {
+	///     Ok(Response::file("video.mp4")?.with_range(&req))
+	/// }
+	/// ```
+	pub fn with_range(mut self, req: &Request) -> Self {
+		let already_encoded = self
+			.headers
+			.as_ref()
+			.map_or(false, |h| h.contains_key("Content-Encoding"));
+
+		if self.status != 200 || self.is_streamed() || already_encoded {
+			return self;
+		}
+
+		self.set_header("Accept-Ranges", "bytes".into());
+
+		let range = match req.range() {
+			Some(range) if req.method == Method::GET => range,
+			_ => return self,
+		};
+
+		let total = self.bytes.len() as u64;
+		let (start, end) = match range.resolve(total) {
+			Some(bounds) => bounds,
+			None => return Self::unsatisfiable_range(total),
+		};
+
+		self.bytes = self.bytes[start as usize..=end as usize].to_vec();
+		self.status = 206;
+		self.status_text = response_types::reason_phrase(206);
+
+		if self
+			.headers
+			.as_ref()
+			.map_or(false, |h| h.contains_key("Content-Length"))
+		{
+			self.set_content_length(self.bytes.len());
+		}
+
+		self.with_content_range(start, end, total)
+	}
+
+	/// Creates a response from a file, streaming only the requested range (see
+	/// [`Response::from_reader`]), with its `Content-Type` guessed from the extension.
+	///
+	/// Without a range, the whole file is sent as a `200 Ok`. Otherwise, a
+	/// `206 Partial Content` is sent, or a `416 Range Not Satisfiable` if the range
+	/// is out of bounds. See [`Range`].
+	pub fn from_file_range(path: impl AsRef<Path>, range: Option<Range>) -> io::Result<Self> {
+		let path = path.as_ref();
+		let mut file = File::open(path)?;
+		let total = file.metadata()?.len();
+		let content_type = path
+			.extension()
+			.and_then(|ext| ext.to_str())
+			.map_or("application/octet-stream", mime_from_extension);
+
+		// The file is streamed, so big ranges aren't kept in memory.
+		let response = match range {
+			Some(range) => {
+				let (start, end) = match range.resolve(total) {
+					Some(bounds) => bounds,
+					None => return Ok(Self::unsatisfiable_range(total)),
+				};
+
+				file.seek(SeekFrom::Start(start))?;
+				Self::from_reader(206, file.take(end - start + 1))
+					.with_content_range(start, end, total)
+			}
+			None => Self::from_reader(200, file),
+		};
+
+		Ok(response
+			.with_header("Accept-Ranges", "bytes".into())
+			.with_content_type(content_type.into()))
+	}
+
+	/// Sets a strong `ETag` header (`"tag"`), returning the response itself.
+	pub fn with_etag(self, tag: &str) -> Self {
+		self.with_header("ETag", format!("\"{}\"", tag))
+	}
+
+	/// Sets a weak `ETag` header (`W/"tag"`), returning the response itself.
+	pub fn with_weak_etag(self, tag: &str) -> Self {
+		self.with_header("ETag", format!("W/\"{}\"", tag))
+	}
+
+	/// Replaces the response with a `304 Not Modified` if the `If-None-Match`
+	/// header of a `GET` or `HEAD` request matches its `ETag`, using weak comparison.
+	/// `If-None-Match: *` matches any successful response.
+	pub fn or_not_modified(self, req: &Request) -> Self {
+		if req.method != Method::GET && req.method != Method::HEAD {
+			return self;
+		}
+
+		let if_none_match = match req.get_header("If-None-Match") {
+			Some(value) => value,
+			None => return self,
+		};
+
+		let etag = self.headers.as_ref().and_then(|h| h.get("ETag"));
+
+		let matches = if if_none_match.trim() == "*" {
+			(200..300).contains(&self.status)
+		} else {
+			etag.map_or(false, |etag| {
+				if_none_match
+					.split(',')
+					.any(|tag| weak_etag_eq(tag.trim(), etag))
+			})
+		};
+
+		if !matches {
+			return self;
+		}
+
+		let headers = etag.map(|etag| crate::headers! { "ETag" => etag });
+		Self::not_modified(vec![], headers, self.version)
+	}
+
 	/// Sets the content length of a reference to a response
 	pub fn set_header(&mut self, key: &'static str, value: String) -> &mut Self {
 		self.headers
@@ -87,6 +709,15 @@ impl Response {
 		self
 	}
 
+	/// Iterates over the headers of the response, yielding nothing if there are none.
+	/// Repeated headers (see [`Headers`]) are yielded once per value.
+	pub fn headers_iter(&self) -> impl Iterator<Item = (&str, &str)> {
+		self.headers
+			.iter()
+			.flatten()
+			.flat_map(|(key, value)| value.split('\n').map(move |line| (*key, line)))
+	}
+
 	/// Sets the content length of a reference to a response
 	pub fn set_content_length(&mut self, len: usize) -> &mut Self {
 		self.set_header("Content-Length", len.to_string())
@@ -95,12 +726,33 @@ impl Response {
 	/// Returns the first lines of the generated response. (everything except the body)
 	/// This function is used internally to create the response.
 	fn prepare_response(&self) -> String {
-		let mut text = format!("{} {} {}\r\n", self.version, self.status, self.status_text);
+		self.prepare_head(self.version)
+	}
 
-		if let Some(headers) = &self.headers {
-			for (key, value) in headers {
-				text.push_str(&format!("{key}: {value}\r\n"));
-			}
+	/// Like [`Response::prepare_response`], but using the version that is actually sent.
+	/// With the `tracing` feature, a warning is logged if it's different from the one set.
+	/// See [`Response::wire_version`].
+	fn prepare_wire_response(&self) -> String {
+		let version = self.wire_version();
+
+		#[cfg(feature = "tracing")]
+		if version != self.version {
+			tracing::warn!(
+				version = ?self.version,
+				sent = %version,
+				"response version is not supported, sending another one instead"
+			);
+		}
+
+		self.prepare_head(version)
+	}
+
+	/// Returns the status line and headers of the response, using `version`.
+	fn prepare_head(&self, version: HttpVersion) -> String {
+		let mut text = format!("{} {} {}\r\n", version, self.status, self.status_text);
+
+		for (key, value) in self.headers_iter() {
+			text.push_str(&format!("{key}: {value}\r\n"));
 		}
 
 		text += "\r\n";
@@ -114,24 +766,57 @@ impl Response {
 		bytes
 	}
 
+	/// Formats the response for tests and debugging. Unlike [`fmt::Display`], the body
+	/// is escaped (e.g. `\x00`, `\n`) instead of converted to UTF-8 lossily, and only
+	/// its first 256 bytes are shown, so binary responses stay readable.
+	pub fn to_debug_string(&self) -> String {
+		let mut text = self.prepare_response();
+		let preview = &self.bytes[..self.bytes.len().min(DEBUG_BODY_PREVIEW)];
+
+		for byte in preview {
+			text.extend(std::ascii::escape_default(*byte).map(char::from));
+		}
+
+		if self.bytes.len() > preview.len() {
+			text += &format!("... ({} more bytes)", self.bytes.len() - preview.len());
+		}
+
+		text
+	}
+
 	/// Gets the length of the response body.
 	pub fn len(&self) -> usize {
 		self.bytes.len()
 	}
 
-	/// Checks if the response body is empty.
+	/// Gets the length of the body before it was compressed,
+	/// which is the same as [`Response::len`] if it wasn't.
+	pub fn original_len(&self) -> usize {
+		self.original_len.unwrap_or(self.bytes.len())
+	}
+
+	/// Gets the length of the body as sent, after compression if it was compressed.
+	pub fn encoded_len(&self) -> usize {
+		self.bytes.len()
+	}
+
+	/// Checks if the response body is empty. Streamed bodies aren't considered empty.
 	pub fn is_empty(&self) -> bool {
-		self.bytes.is_empty()
+		self.bytes.is_empty() && !self.is_streamed()
 	}
 
 	/// Adds optional but useful headers to a response.
-	/// This includes the Content-Length header, Date header and Server header.
+	/// This includes the Content-Length header (unless the body is streamed),
+	/// Date header and Server header.
 	pub fn with_default_headers(mut self) -> Self {
 		let now = chrono::Utc::now().to_rfc2822();
 		let len = self.len();
 
-		self.set_header("Content-Length", len.to_string())
-			.set_header("Date", now)
+		if !self.is_streamed() {
+			self.set_content_length(len);
+		}
+
+		self.set_header("Date", now)
 			.set_header("Server", "Snowboard".into());
 
 		self
@@ -147,6 +832,34 @@ impl Response {
 	}
 }
 
+/// Writes a streamed body using the chunked transfer encoding, flushing every chunk.
+fn write_chunked<T: Write>(reader: &BodyReader, stream: &mut T) -> io::Result<()> {
+	let mut buffer = vec![0; reader.chunk_size];
+	let mut reader = reader.reader.lock().unwrap_or_else(|e| e.into_inner());
+
+	loop {
+		let read = match reader.read(&mut buffer) {
+			Ok(0) => break,
+			Ok(read) => read,
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+			Err(e) => return Err(e),
+		};
+
+		write!(stream, "{:x}\r\n", read)?;
+		stream.write_all(&buffer[..read])?;
+		stream.write_all(b"\r\n")?;
+		stream.flush()?;
+	}
+
+	stream.write_all(b"0\r\n\r\n")?;
+	stream.flush()
+}
+
+/// Compares two entity tags ignoring the weak indicator (`W/`).
+fn weak_etag_eq(a: &str, b: &str) -> bool {
+	a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
 impl From<Response> for Vec<u8> {
 	fn from(mut res: Response) -> Self {
 		res.to_bytes()
@@ -169,6 +882,8 @@ impl Default for Response {
 			status_text: "Ok",
 			bytes: vec![],
 			headers: None,
+			original_len: None,
+			reader: None,
 		}
 	}
 }
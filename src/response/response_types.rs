@@ -17,6 +17,23 @@ macro_rules! create_response_types {
             }
         )*
         }
+
+		/// Gets the reason phrase of a status code, like `"Not Found"` for `404`,
+		/// or an empty string if it's unknown.
+		///
+		/// # Example
+		/// ```rust
+		/// use snowboard::{reason_phrase, Response, DEFAULT_HTTP_VERSION};
+		///
+		/// let response = Response::new(DEFAULT_HTTP_VERSION, 429, reason_phrase(429), vec![], None);
+		/// assert_eq!(response.status_text, "Too Many Requests");
+		/// ```
+		pub fn reason_phrase(code: u16) -> &'static str {
+			match code {
+				$($code => $text,)*
+				_ => "",
+			}
+		}
     };
 }
 
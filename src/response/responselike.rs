@@ -1,9 +1,12 @@
 //! A module that provides and handles traits which can help in serializing and deserializing
 //! response into different data types.
 
-use super::Response;
+use super::{response_types::reason_phrase, Headers, Response};
 
 /// A trait for everything that can be converted into a Response.
+///
+/// `Option<T>` responds with `T`, or with an empty `404 Not Found` for `None`.
+/// `(u16, T)` and `(u16, T, Headers)` respond with `T` using the given status.
 pub trait ResponseLike {
 	/// Converts `self` into a `Response`.
 	fn to_response(self) -> Response;
@@ -58,6 +61,80 @@ where
 	}
 }
 
+// Useful for lookups: `None` becomes an empty `404 Not Found`, which can be
+// customized with `Server::with_not_found_handler`.
+impl<T: ResponseLike> ResponseLike for Option<T> {
+	fn to_response(self) -> Response {
+		match self {
+			Some(res) => res.to_response(),
+			None => crate::response!(not_found),
+		}
+	}
+}
+
+// Merges the headers into the response of `T`, replacing repeated ones.
+// `Set-Cookie` values are kept, so cookies set by both aren't lost.
+impl<T: ResponseLike> ResponseLike for (T, Headers) {
+	fn to_response(self) -> Response {
+		let (res, headers) = self;
+		let mut res = res.to_response();
+		let merged = res.headers.get_or_insert_with(Headers::new);
+
+		for (key, value) in headers {
+			match merged.get_mut(key) {
+				Some(cookies) if key.eq_ignore_ascii_case("Set-Cookie") => {
+					cookies.push('\n');
+					cookies.push_str(&value);
+				}
+				_ => {
+					merged.insert(key, value);
+				}
+			}
+		}
+
+		res
+	}
+}
+
+// Overrides the status of the response of `T`, like `(201, "Created!")`.
+// The status text is looked up from the known status codes, and left empty otherwise.
+impl<T: ResponseLike> ResponseLike for (u16, T) {
+	fn to_response(self) -> Response {
+		let (status, res) = self;
+		let mut res = res.to_response();
+
+		res.status = status;
+		res.status_text = reason_phrase(status);
+
+		res
+	}
+}
+
+// Same as `(u16, T)`, also merging the headers like `(T, Headers)`.
+impl<T: ResponseLike> ResponseLike for (u16, T, Headers) {
+	fn to_response(self) -> Response {
+		let (status, res, headers) = self;
+
+		(status, (res, headers)).to_response()
+	}
+}
+
+// Makes `std::io::Result<T>` usable as a response, see the `Result` implementation.
+impl ResponseLike for std::io::Error {
+	fn to_response(self) -> Response {
+		let bytes = self.to_string().into_bytes();
+		let headers = crate::headers! {
+			"Content-Type" => "text/plain; charset=utf-8",
+		};
+
+		match self.kind() {
+			std::io::ErrorKind::NotFound => crate::response!(not_found, bytes, headers),
+			std::io::ErrorKind::PermissionDenied => crate::response!(forbidden, bytes, headers),
+			_ => crate::response!(internal_server_error, bytes, headers),
+		}
+	}
+}
+
 #[cfg(feature = "json")]
 impl ResponseLike for serde_json::Error {
 	#[inline]
@@ -74,6 +151,66 @@ impl ResponseLike for serde_json::Error {
 	}
 }
 
+#[cfg(feature = "json")]
+impl ResponseLike for serde_urlencoded::de::Error {
+	#[inline]
+	fn to_response(self) -> Response {
+		let bytes = self.to_string().into_bytes();
+
+		crate::response!(
+			bad_request,
+			bytes,
+			crate::headers! {
+				"Content-Type" => "text/plain; charset=utf-8",
+			}
+		)
+	}
+}
+
+/// Wraps any serializable value, so it can be returned as a JSON response
+/// with `Content-Type: application/json`.
+/// Values that fail to serialize become a `500 Internal Server Error`.
+///
+/// # Example
+/// ```rust
+/// use serde::Serialize;
+/// use snowboard::{Json, Request};
+///
+/// #[derive(Serialize)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// fn handler(_: Request) -> Json<User> {
+///     Json(User { name: "snow".into() })
+/// }
+/// ```
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Json<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: serde::Serialize> ResponseLike for Json<T> {
+	fn to_response(self) -> Response {
+		match serde_json::to_vec(&self.0) {
+			Ok(bytes) => crate::response!(
+				ok,
+				bytes,
+				crate::headers! {
+					"Content-Type" => "application/json; charset=utf-8",
+				}
+			),
+			Err(e) => crate::response!(
+				internal_server_error,
+				e.to_string(),
+				crate::headers! {
+					"Content-Type" => "text/plain; charset=utf-8",
+				}
+			),
+		}
+	}
+}
+
 #[cfg(feature = "json")]
 impl ResponseLike for serde_json::Value {
 	#[inline]
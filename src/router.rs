@@ -0,0 +1,207 @@
+//! A module that provides a simple router, matching requests by method and path.
+
+use std::{collections::HashMap, sync::Arc};
+
+use crate::url::percent_decode;
+use crate::{Method, Request, Response, ResponseLike};
+
+/// Parameters bound by a route pattern, like `id` in `/users/:id`.
+/// Values are percent-decoded.
+pub type Params = HashMap<String, String>;
+
+/// A shared route handler.
+type RouteHandler = Arc<dyn Fn(Request, Params) -> Response + Send + Sync>;
+
+/// A segment of a route pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+	/// A segment that must match exactly.
+	Static(String),
+	/// A segment bound to a parameter (`:name`).
+	Param(String),
+	/// The rest of the path, bound to a parameter (`*name`).
+	Wildcard(String),
+}
+
+/// A registered route.
+#[derive(Clone)]
+struct Route {
+	/// Method the route answers to.
+	method: Method,
+	/// Segments of the path pattern.
+	pattern: Vec<Segment>,
+	/// Handler called when the route matches.
+	handler: RouteHandler,
+}
+
+impl Route {
+	/// Matches the segments of a path against the pattern of the route,
+	/// returning the bound parameters.
+	fn matches(&self, path: &[&str]) -> Option<HashMap<&str, String>> {
+		let mut params = HashMap::new();
+
+		for (i, segment) in self.pattern.iter().enumerate() {
+			match (segment, path.get(i)) {
+				(Segment::Static(expected), Some(part)) if expected == part => {}
+				(Segment::Param(name), Some(part)) => {
+					params.insert(name.as_str(), percent_decode(part, false).into_owned());
+				}
+				(Segment::Wildcard(name), Some(_)) => {
+					let rest: Vec<_> = path[i..]
+						.iter()
+						.map(|part| percent_decode(part, false))
+						.collect();
+
+					params.insert(name.as_str(), rest.join("/"));
+					return Some(params);
+				}
+				_ => return None,
+			}
+		}
+
+		if path.len() == self.pattern.len() {
+			Some(params)
+		} else {
+			None
+		}
+	}
+
+	/// Gets how generic the pattern is, as the amount of wildcards and parameters.
+	/// Lower is more specific.
+	fn genericity(&self) -> (usize, usize) {
+		self.pattern
+			.iter()
+			.fold((0, 0), |(wildcards, params), segment| match segment {
+				Segment::Static(_) => (wildcards, params),
+				Segment::Param(_) => (wildcards, params + 1),
+				Segment::Wildcard(_) => (wildcards + 1, params),
+			})
+	}
+}
+
+/// Maps requests to handlers by method and path pattern.
+/// Patterns are split by `/`, and segments starting with `:` (like `/users/:id`)
+/// match any segment, binding it to a parameter passed to the handler.
+/// A last segment starting with `*` (like `/static/*path`) matches the rest of
+/// the path (at least one segment), binding its segments joined by `/`.
+///
+/// If several routes match, the most specific one is used: the one with fewer
+/// wildcards, then fewer parameters, then the first one added.
+///
+/// Routers can be registered on a server with [`crate::Server::with_router`] or
+/// [`crate::Server::route`], or used as a handler directly with [`Router::handle`].
+///
+/// # Example
+/// ```rust
+/// use snowboard::{response, Method, Router, Server};
+///
+/// let router = Router::new()
+///     .route(Method::GET, "/", |_, _| response!(ok, "Home"))
+///     .route(Method::GET, "/users/:id", |_, params| {
+///         response!(ok, format!("User {}", params["id"]))
+///     });
+///
+/// let server = Server::new("localhost:8080").expect("failed to start server");
+/// // server.run(move |req| router.handle(req));
+/// ```
+#[derive(Clone, Default)]
+pub struct Router {
+	/// Registered routes, in order.
+	routes: Vec<Route>,
+}
+
+impl Router {
+	/// Creates a router with no routes.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a route, returning the router itself.
+	pub fn route<T: ResponseLike>(
+		mut self,
+		method: Method,
+		pattern: &str,
+		handler: impl Fn(Request, Params) -> T + Send + Sync + 'static,
+	) -> Self {
+		let pattern = pattern
+			.split('/')
+			.filter(|segment| !segment.is_empty())
+			.map(|segment| {
+				if let Some(name) = segment.strip_prefix(':') {
+					Segment::Param(name.into())
+				} else if let Some(name) = segment.strip_prefix('*') {
+					Segment::Wildcard(name.into())
+				} else {
+					Segment::Static(segment.into())
+				}
+			})
+			.collect();
+
+		self.routes.push(Route {
+			method,
+			pattern,
+			handler: Arc::new(move |request, params| handler(request, params).to_response()),
+		});
+
+		self
+	}
+
+	/// Checks if no routes were added.
+	pub fn is_empty(&self) -> bool {
+		self.routes.is_empty()
+	}
+
+	/// Calls the handler of the route matching the request.
+	///
+	/// Responds with an empty `405 Method Not Allowed` (with an `Allow` header) if only
+	/// routes for other methods match the path, or an empty `404 Not Found` if no route
+	/// matches it. See [`crate::Server::with_not_found_handler`] to customize them.
+	pub fn handle(&self, request: Request) -> Response {
+		self.dispatch(request)
+			.unwrap_or_else(|_| crate::response!(not_found))
+	}
+
+	/// Like [`Router::handle`], but giving the request back if no route matches its path,
+	/// so another handler can be used.
+	// The request is given back as is, so boxing it would only add an allocation.
+	#[allow(clippy::result_large_err)]
+	pub fn dispatch(&self, request: Request) -> Result<Response, Request> {
+		let url = request.parse_url();
+		let mut allowed = vec![];
+		let mut found: Option<(&Route, HashMap<&str, String>)> = None;
+
+		for route in &self.routes {
+			let params = match route.matches(&url.path) {
+				Some(params) => params,
+				None => continue,
+			};
+
+			if route.method != request.method {
+				allowed.push(route.method);
+				continue;
+			}
+
+			match &found {
+				Some((best, _)) if best.genericity() <= route.genericity() => {}
+				_ => found = Some((route, params)),
+			}
+		}
+
+		let found = found.map(|(route, params)| {
+			let params: Params = params
+				.into_iter()
+				.map(|(name, value)| (name.to_string(), value))
+				.collect();
+
+			(Arc::clone(&route.handler), params)
+		});
+
+		match found {
+			Some((handler, params)) => Ok(handler(request, params)),
+			None if !allowed.is_empty() => {
+				Ok(crate::response!(method_not_allowed).with_allow(&allowed))
+			}
+			None => Err(request),
+		}
+	}
+}
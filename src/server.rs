@@ -1,17 +1,44 @@
 //! A module that provides server implementation for the library.
 
+use crate::request::{decode_chunked, is_chunked};
+use crate::router::Params;
+use crate::Cors;
+#[cfg(feature = "compression")]
+use crate::Encoding;
+use crate::HttpVersion;
+use crate::Method;
 use crate::Request;
+use crate::Response;
 use crate::ResponseLike;
+use crate::Router;
 
 /// The size of the buffer used to read incoming requests.
 /// It's set to 8KiB by default.
 pub const DEFAULT_BUFFER_SIZE: usize = 1024 * 8;
 
 use std::{
-	io,
+	collections::BTreeMap,
+	io::{self, IoSlice, Read, Write},
 	net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+	sync::{
+		atomic::{AtomicBool, AtomicU64, Ordering},
+		mpsc, Arc, Condvar, Mutex, RwLock,
+	},
+	time::{Duration, Instant},
 };
 
+/// How often the listener is checked for new connections when there's an accept timeout.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Start of the connection preface sent by HTTP/2 clients.
+const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n";
+
+/// How often the accept loop checks if it has to stop (because a handler panicked
+/// or the server is shutting down) when no accept timeout is set.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+#[cfg(feature = "tls")]
+use crate::{Identity, TlsVersion};
 #[cfg(feature = "tls")]
 use native_tls::{TlsAcceptor, TlsStream};
 
@@ -23,11 +50,88 @@ pub type Stream = TcpStream;
 #[cfg(feature = "tls")]
 pub type Stream = TlsStream<TcpStream>;
 
+/// Information about the TLS session of a connection.
+///
+/// `native_tls` doesn't expose the negotiated protocol version or cipher,
+/// so only the peer certificate is available.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+	/// The DER-encoded certificate sent by the client, if any.
+	///
+	/// `native_tls` can't configure a `TlsAcceptor` to request or verify client
+	/// certificates, so this is usually `None`. Mutual TLS has to be handled by
+	/// a proxy in front of the server.
+	pub peer_certificate: Option<Vec<u8>>,
+}
+
+/// Extra information about a connection stream.
+pub trait StreamExt: io::Write {
+	/// Gets the TLS session information, or `None` for plaintext connections.
+	fn tls_info(&self) -> Option<TlsInfo>;
+
+	/// Writes every buffer, in order, using as few writes as possible.
+	/// See [`crate::Response::send_to_stream`].
+	fn write_coalesced(&mut self, bufs: &[&[u8]]) -> io::Result<()>;
+}
+
+impl StreamExt for TcpStream {
+	#[inline]
+	fn tls_info(&self) -> Option<TlsInfo> {
+		None
+	}
+
+	fn write_coalesced(&mut self, bufs: &[&[u8]]) -> io::Result<()> {
+		// `IoSlice::advance_slices` is newer than the MSRV, so slices are advanced by hand.
+		let mut bufs: Vec<&[u8]> = bufs.iter().copied().filter(|b| !b.is_empty()).collect();
+		let mut start = 0;
+
+		while start < bufs.len() {
+			let slices: Vec<IoSlice<'_>> = bufs[start..].iter().map(|b| IoSlice::new(b)).collect();
+
+			let mut written = match self.write_vectored(&slices) {
+				Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero)),
+				Ok(written) => written,
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			};
+
+			while start < bufs.len() && written >= bufs[start].len() {
+				written -= bufs[start].len();
+				start += 1;
+			}
+
+			if start < bufs.len() {
+				bufs[start] = &bufs[start][written..];
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(feature = "tls")]
+impl StreamExt for TlsStream<TcpStream> {
+	fn tls_info(&self) -> Option<TlsInfo> {
+		let peer_certificate = self
+			.peer_certificate()
+			.ok()
+			.flatten()
+			.and_then(|cert| cert.to_der().ok());
+
+		Some(TlsInfo { peer_certificate })
+	}
+
+	fn write_coalesced(&mut self, bufs: &[&[u8]]) -> io::Result<()> {
+		// Vectored writes aren't supported, but a single buffer is sent in one TLS record.
+		self.write_all(&bufs.concat())
+	}
+}
+
 #[cfg(feature = "websocket")]
 use crate::ws::{maybe_websocket, WebSocket};
 
 #[cfg(feature = "async")]
-use std::future::Future;
+use std::{future::Future, pin::Pin};
 
 /// Single threaded listener made for simpler servers.
 pub struct Server {
@@ -37,9 +141,68 @@ pub struct Server {
 	buffer_size: usize,
 	/// It stores the default HTTP/HTTPS request headers.
 	insert_default_headers: bool,
+	/// It stores whether POST requests can override their method.
+	method_override: bool,
+	/// It stores the maximum time a request can take to be read and handled.
+	request_timeout: Option<Duration>,
+	/// It stores the amount of threads used to run handlers, if limited.
+	handler_pool: Option<usize>,
+	/// It stores the `Allow` header sent for `OPTIONS *` requests, if enabled.
+	server_options: Option<Arc<str>>,
+	/// It stores the CORS configuration, if enabled.
+	cors: Option<Arc<Cors>>,
+	/// It stores the idle timeout and maximum requests of kept-alive connections.
+	keep_alive: Option<(Duration, usize)>,
+	/// It stores how long idle kept-alive connections stay open after their timeout.
+	close_grace: Option<Duration>,
+	/// It stores the maximum time to wait for a new connection.
+	accept_timeout: Option<Duration>,
+	/// It stores the maximum time a single read from a connection can take.
+	read_timeout: Option<Duration>,
+	/// It stores the limit of connections handled at once.
+	connection_limit: Option<ConnectionLimit>,
+	/// It stores the maximum time to receive the head of a request.
+	header_timeout: Option<Duration>,
+	/// It stores the maximum size of the head of a request.
+	max_head_size: Option<usize>,
+	/// It stores the path answered with `200 Ok` for health checks.
+	health_check: Option<&'static str>,
+	/// It stores the path answered with the server metrics.
+	metrics_endpoint: Option<&'static str>,
+	/// It stores the maximum amount of bytes read from a single connection.
+	max_connection_bytes: Option<usize>,
+	/// It stores the function applied to every response before sending it.
+	response_mapper: Option<ResponseMapper>,
+	/// It stores the functions run before the handler, in order.
+	middleware: Vec<Middleware>,
+	/// It stores the function taking over upgraded connections.
+	upgrade_handler: Option<UpgradeHandler>,
+	/// It stores the function giving the links sent as early hints.
+	early_hints: Option<EarlyHints>,
+	/// It stores the functions run on the responses of the handler, in order.
+	after_middleware: Vec<AfterMiddleware>,
+	/// It stores the id given to the next connection, if enabled.
+	connection_ids: Option<AtomicU64>,
+	/// It stores the routes tried before the handler.
+	router: Router,
+	/// It stores the handler used for empty `404 Not Found` responses.
+	not_found_handler: Option<SharedHandler>,
+	/// It stores the handler used for empty `405 Method Not Allowed` responses.
+	method_not_allowed_handler: Option<SharedHandler>,
+	/// It stores whether the server stops when a handler panics.
+	abort_on_panic: bool,
+	/// It stores the encodings responses can be compressed with, by priority.
+	#[cfg(feature = "compression")]
+	compression: Option<Arc<[Encoding]>>,
+	/// It stores whether requests and connections are traced.
+	#[cfg(feature = "tracing")]
+	tracing: bool,
 	/// It stores the TlsAcceptor struct when the tls feature is enabled.
 	#[cfg(feature = "tls")]
 	tls_acceptor: TlsAcceptor,
+	/// It stores the host and port plaintext requests are redirected to.
+	#[cfg(feature = "tls")]
+	tls_redirect_target: Option<(String, u16)>,
 	#[cfg(feature = "websocket")]
 	/// It stores the WebSocket configuration for the HTTP/HTTPS server.
 	ws_handler: Option<(&'static str, fn(WebSocket<&mut Stream>))>,
@@ -51,27 +214,183 @@ impl Server {
 	/// The server will listen on the given address.
 	#[cfg(not(feature = "tls"))]
 	pub fn new(addr: impl ToSocketAddrs) -> io::Result<Self> {
-		Ok(Self {
-			acceptor: TcpListener::bind(addr)?,
+		Ok(Self::from_listener(TcpListener::bind(addr)?))
+	}
+
+	/// Create a new server instance from an already bound listener.
+	/// See [`Server::listen_fd`] for systemd socket activation.
+	#[cfg(not(feature = "tls"))]
+	pub fn from_listener(acceptor: TcpListener) -> Self {
+		Self {
+			acceptor,
 			buffer_size: DEFAULT_BUFFER_SIZE,
 			#[cfg(feature = "websocket")]
 			ws_handler: None,
 			insert_default_headers: false,
-		})
+			method_override: false,
+			request_timeout: None,
+			handler_pool: None,
+			server_options: None,
+			cors: None,
+			keep_alive: None,
+			close_grace: None,
+			accept_timeout: None,
+			read_timeout: None,
+			connection_limit: None,
+			header_timeout: None,
+			max_head_size: None,
+			health_check: None,
+			metrics_endpoint: None,
+			max_connection_bytes: None,
+			response_mapper: None,
+			middleware: vec![],
+			upgrade_handler: None,
+			early_hints: None,
+			after_middleware: vec![],
+			connection_ids: None,
+			router: Router::new(),
+			not_found_handler: None,
+			method_not_allowed_handler: None,
+			abort_on_panic: false,
+			#[cfg(feature = "compression")]
+			compression: None,
+			#[cfg(feature = "tracing")]
+			tracing: false,
+		}
 	}
 
 	/// Create a new server instance with TLS.
 	/// The server will listen on the given address.
+	///
+	/// Session resumption depends on the platform's TLS library, since `native-tls`
+	/// has no way to configure session caches or tickets.
 	#[cfg(feature = "tls")]
 	pub fn new_with_tls(addr: impl ToSocketAddrs, tls_acceptor: TlsAcceptor) -> io::Result<Self> {
-		Ok(Self {
-			acceptor: TcpListener::bind(addr)?,
+		Ok(Self::from_listener_with_tls(
+			TcpListener::bind(addr)?,
+			tls_acceptor,
+		))
+	}
+
+	/// Create a new server instance with TLS from an identity, refusing clients
+	/// that use a TLS version older than `min_version` (usually `TlsVersion::Tlsv12`).
+	///
+	/// The minimum version can't be changed once a `TlsAcceptor` is built,
+	/// so this is only available for identities.
+	#[cfg(feature = "tls")]
+	pub fn new_with_identity(
+		addr: impl ToSocketAddrs,
+		identity: Identity,
+		min_version: TlsVersion,
+	) -> io::Result<Self> {
+		let tls_acceptor = TlsAcceptor::builder(identity)
+			.min_protocol_version(Some(min_version))
+			.build()
+			.map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+		Self::new_with_tls(addr, tls_acceptor)
+	}
+
+	/// Create a new server instance with TLS from an already bound listener.
+	/// See [`Server::listen_fd`] for systemd socket activation.
+	#[cfg(feature = "tls")]
+	pub fn from_listener_with_tls(acceptor: TcpListener, tls_acceptor: TlsAcceptor) -> Self {
+		Self {
+			acceptor,
 			buffer_size: DEFAULT_BUFFER_SIZE,
 			tls_acceptor,
+			tls_redirect_target: None,
 			#[cfg(feature = "websocket")]
 			ws_handler: None,
 			insert_default_headers: false,
-		})
+			method_override: false,
+			request_timeout: None,
+			handler_pool: None,
+			server_options: None,
+			cors: None,
+			keep_alive: None,
+			close_grace: None,
+			accept_timeout: None,
+			read_timeout: None,
+			connection_limit: None,
+			header_timeout: None,
+			max_head_size: None,
+			health_check: None,
+			metrics_endpoint: None,
+			max_connection_bytes: None,
+			response_mapper: None,
+			middleware: vec![],
+			upgrade_handler: None,
+			early_hints: None,
+			after_middleware: vec![],
+			connection_ids: None,
+			router: Router::new(),
+			not_found_handler: None,
+			method_not_allowed_handler: None,
+			abort_on_panic: false,
+			#[cfg(feature = "compression")]
+			compression: None,
+			#[cfg(feature = "tracing")]
+			tracing: false,
+		}
+	}
+
+	/// Gets the listening socket passed by systemd socket activation, if any.
+	///
+	/// Since this crate doesn't use unsafe code, converting it is left to the caller:
+	/// ```rust
+	/// use std::{net::TcpListener, os::unix::io::FromRawFd};
+	/// use snowboard::Server;
+	///
+	/// if let Some(fd) = Server::listen_fd() {
+	///     let listener = unsafe { TcpListener::from_raw_fd(fd) };
+	///     let server = Server::from_listener(listener);
+	/// }
+	/// ```
+	#[cfg(unix)]
+	pub fn listen_fd() -> Option<std::os::unix::io::RawFd> {
+		/// The first file descriptor passed by systemd.
+		const SD_LISTEN_FDS_START: std::os::unix::io::RawFd = 3;
+
+		let pid: u32 = std::env::var("LISTEN_PID").ok()?.parse().ok()?;
+		let fds: u32 = std::env::var("LISTEN_FDS").ok()?.parse().ok()?;
+
+		if pid == std::process::id() && fds >= 1 {
+			Some(SD_LISTEN_FDS_START)
+		} else {
+			None
+		}
+	}
+
+	/// Consumes the server, returning its listener.
+	pub fn into_listener(self) -> TcpListener {
+		self.acceptor
+	}
+
+	/// Consumes the server, returning the file descriptor of its listener,
+	/// so it can be handed off to another process (e.g. from a supervisor that
+	/// binds the address and then execs the actual server).
+	///
+	/// The server can't be used afterwards, so the handoff goes like this:
+	/// 1. Take the file descriptor with this method, instead of running the server.
+	/// 2. Clear its `FD_CLOEXEC` flag (e.g. using `libc::fcntl`) so it's inherited,
+	///    and move it to fd 3 with `dup2`.
+	/// 3. `exec` the new program (or spawn it and pass the fd on) with `LISTEN_FDS=1`
+	///    and `LISTEN_PID` set to the pid of the process that will use it.
+	/// 4. On the other side, get the fd with [`Server::listen_fd`] and create the
+	///    server with [`Server::from_listener`].
+	#[cfg(unix)]
+	pub fn into_listener_fd(self) -> std::os::unix::io::RawFd {
+		use std::os::unix::io::IntoRawFd;
+		self.acceptor.into_raw_fd()
+	}
+
+	/// Sets the HTTPS endpoint plaintext requests are redirected to, in case it's
+	/// not the address the server listens on (e.g. behind a proxy or port mapping).
+	#[cfg(feature = "tls")]
+	pub fn with_tls_redirect_target(mut self, host: &str, port: u16) -> Self {
+		self.tls_redirect_target = Some((host.into(), port));
+		self
 	}
 
 	/// Enables automatic insertion of default headers in responses.
@@ -81,6 +400,311 @@ impl Server {
 		self
 	}
 
+	/// Enables HTTP method overriding for `POST` requests, so HTML forms can
+	/// send other methods. See [`Request::method_override`].
+	pub fn with_method_override(mut self, enabled: bool) -> Self {
+		self.method_override = enabled;
+		self
+	}
+
+	/// Sets a deadline for every request. Reading the request is limited by
+	/// it, and if the handler doesn't finish in time a `503 Service Unavailable`
+	/// response is sent instead. Note that the handler itself keeps running.
+	pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+		self.request_timeout = Some(timeout);
+		self
+	}
+
+	/// Runs handlers in a fixed pool of `size` threads instead of a new thread
+	/// per request, so CPU-bound handlers don't contend with each other.
+	/// Only used by [`Server::run`].
+	pub fn with_handler_pool(mut self, size: usize) -> Self {
+		self.handler_pool = Some(size.max(1));
+		self
+	}
+
+	/// Answers `OPTIONS *` requests (asking about the server as a whole)
+	/// automatically, with a `200 Ok` and an `Allow` header listing `methods`.
+	/// Otherwise, they are passed to the handler like any other request.
+	pub fn with_server_options(mut self, methods: &[Method]) -> Self {
+		let allow = methods
+			.iter()
+			.map(Method::to_string)
+			.collect::<Vec<String>>()
+			.join(", ");
+
+		self.server_options = Some(allow.into());
+		self
+	}
+
+	/// Enables CORS, adding `Access-Control-*` headers to responses and
+	/// answering preflight requests automatically. See [`Cors`].
+	pub fn with_cors(mut self, cors: Cors) -> Self {
+		self.cors = Some(Arc::new(cors));
+		self
+	}
+
+	/// Keeps connections open for up to `max_requests` requests, closing them after
+	/// `idle_timeout` without new requests. HTTP/1.1 connections are kept open unless
+	/// they send `Connection: close`, and HTTP/1.0 ones only with `Connection: keep-alive`.
+	/// See [`Request::wants_keep_alive`].
+	/// Kept-alive responses advertise this with a `Keep-Alive` header.
+	///
+	/// Only used by [`Server::run`]. Note that with [`Server::with_handler_pool`],
+	/// a kept-alive connection occupies a thread while it's open.
+	pub fn with_keep_alive(mut self, idle_timeout: Duration, max_requests: usize) -> Self {
+		self.keep_alive = Some((idle_timeout, max_requests));
+		self
+	}
+
+	/// Keeps idle kept-alive connections open for `grace` after the advertised idle
+	/// timeout, so requests sent by clients reusing a connection right as it
+	/// times out are still served instead of failing. Disabled by default.
+	///
+	/// Only used with [`Server::with_keep_alive`].
+	pub fn with_close_grace(mut self, grace: Duration) -> Self {
+		self.close_grace = Some(grace);
+		self
+	}
+
+	/// Makes [`Server::try_accept`] return an `io::ErrorKind::TimedOut` error if no
+	/// connection arrives within `timeout`, so loops can do other work in between.
+	pub fn with_accept_timeout(mut self, timeout: Duration) -> Self {
+		self.accept_timeout = Some(timeout);
+		self
+	}
+
+	/// Compresses responses using the encoding the client prefers (`Accept-Encoding`)
+	/// among `priority`. Encodings the client values equally are picked in that order.
+	/// See [`Encoding::DEFAULT_PRIORITY`].
+	#[cfg(feature = "compression")]
+	pub fn with_compression(mut self, priority: &[Encoding]) -> Self {
+		self.compression = Some(priority.into());
+		self
+	}
+
+	/// Traces requests and connections with the `tracing` crate. Each request gets an
+	/// `http.request` span with its `method`, `path` and response `status`, and opened
+	/// and closed connections are logged as `DEBUG` events with the client's `ip`.
+	#[cfg(feature = "tracing")]
+	pub fn with_tracing(mut self, enabled: bool) -> Self {
+		self.tracing = enabled;
+		self
+	}
+
+	/// Limits the amount of connections handled at once to `max` (at least 1). When it's
+	/// reached, new connections wait to be accepted until another one is closed.
+	/// Kept-alive connections count until they're closed.
+	///
+	/// See [`Server::connection_limit`] to monitor the connections in use.
+	pub fn with_max_connections(mut self, max: usize) -> Self {
+		self.connection_limit = Some(ConnectionLimit::new(max.max(1)));
+		self
+	}
+
+	/// Gets the limit of connections set with [`Server::with_max_connections`], which
+	/// can be kept to check the amount of connections in use while the server runs.
+	pub fn connection_limit(&self) -> Option<ConnectionLimit> {
+		self.connection_limit.clone()
+	}
+
+	/// Closes connections when a single read from them takes longer than `timeout`,
+	/// like when a client connects and never sends anything. No timeout is used by default.
+	///
+	/// If [`Server::with_request_timeout`] is also set, the shortest one is used.
+	/// While waiting for more requests on kept-alive connections, the idle timeout
+	/// is used instead (see [`Server::with_keep_alive`]).
+	pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+		self.read_timeout = Some(timeout);
+		self
+	}
+
+	/// Requires the head of every request (the request line and headers) to arrive
+	/// within `timeout`, responding `408 Request Timeout` and closing the connection
+	/// otherwise. This protects the server against clients sending headers very slowly
+	/// (slowloris attacks), independently of the read timeout.
+	///
+	/// Without it, the head of a request is read using a single read call.
+	pub fn with_header_timeout(mut self, timeout: Duration) -> Self {
+		self.header_timeout = Some(timeout);
+		self
+	}
+
+	/// Limits the size of the head of a request (the request line and headers, before
+	/// `\r\n\r\n`) to `max` bytes, responding `431 Request Header Fields Too Large`,
+	/// or `414 URI Too Long` if the request line alone is too long, and closing the connection.
+	///
+	/// The head is read until it's complete or exceeds the limit, so oversized heads
+	/// are never buffered. Note that heads are also limited by the buffer size.
+	pub fn with_max_head_size(mut self, max: usize) -> Self {
+		self.max_head_size = Some(max);
+		self
+	}
+
+	/// Applies `mapper` to every response right before sending it, including
+	/// responses not generated by the handler (like timeouts or health checks),
+	/// e.g. to add headers. Responses to requests that couldn't be read are not included.
+	///
+	/// Calling it again replaces the previous function.
+	pub fn map_response(
+		mut self,
+		mapper: impl Fn(Response) -> Response + Send + Sync + 'static,
+	) -> Self {
+		self.response_mapper = Some(Arc::new(mapper));
+		self
+	}
+
+	/// Sends a `103 Early Hints` response with the `Link` values `hints` gives for a
+	/// request (like `</style.css>; rel=preload; as=style`) before running the handler,
+	/// so clients can start loading them while the final response is being made.
+	///
+	/// Nothing is sent if there are no links, or to HTTP/1.0 clients, which don't
+	/// support interim responses. Runs after the middleware.
+	///
+	/// # Example
+	/// ```rust
+	/// use snowboard::Server;
+	///
+	/// let server = Server::new("localhost:8080")
+	///     .expect("failed to start server")
+	///     .with_early_hints(|req| match req.url.as_str() {
+	///         "/" => vec!["</style.css>; rel=preload; as=style".into()],
+	///         _ => vec![],
+	///     });
+	/// ```
+	pub fn with_early_hints(
+		mut self,
+		hints: impl Fn(&Request) -> Vec<String> + Send + Sync + 'static,
+	) -> Self {
+		self.early_hints = Some(Arc::new(hints));
+		self
+	}
+
+	/// Adds a function run before the handler, which can modify the request
+	/// (e.g. to add headers) or answer it directly by returning a response
+	/// (e.g. for failed auth checks), skipping the handler and the next middleware.
+	///
+	/// Middleware runs in the order it was added, after requests answered by the
+	/// server itself (like health checks or CORS preflight requests).
+	pub fn with_middleware(
+		mut self,
+		middleware: impl Fn(&mut Request) -> Option<Response> + Send + Sync + 'static,
+	) -> Self {
+		self.middleware.push(Arc::new(middleware));
+		self
+	}
+
+	/// Adds a function run on every response of the handler (or of middleware added
+	/// with [`Server::with_middleware`]), in the order they were added.
+	/// See [`Server::map_response`] to also include responses generated by the server.
+	pub fn with_after_middleware(
+		mut self,
+		middleware: impl Fn(&mut Response) + Send + Sync + 'static,
+	) -> Self {
+		self.after_middleware.push(Arc::new(middleware));
+		self
+	}
+
+	/// Limits the total size of the requests received on a single (kept-alive)
+	/// connection. Once the limit is exceeded, the connection is closed with a
+	/// `413 Payload Too Large` response.
+	///
+	/// Requests are counted as received, see [`Request::bytes_received`].
+	/// Async servers ([`Server::run_async`] and [`Server::run_until`]) answer a single
+	/// request per connection, so the limit applies to that request.
+	pub fn with_max_connection_bytes(mut self, max: usize) -> Self {
+		self.max_connection_bytes = Some(max);
+		self
+	}
+
+	/// Gives every connection an id, available in [`Request::connection_id`],
+	/// so requests of the same kept-alive connection can be correlated (e.g. in logs).
+	/// Ids are assigned in order, starting from 0.
+	pub fn with_connection_id(mut self) -> Self {
+		self.connection_ids = Some(AtomicU64::new(0));
+		self
+	}
+
+	/// Adds a route, tried before the handler given to [`Server::run`] (or
+	/// [`Server::run_async`]), which is only called for requests no route matches.
+	/// Paths matched by routes for other methods get a `405 Method Not Allowed` instead.
+	/// See [`Router`] for the supported patterns, like `/users/:id`.
+	///
+	/// # Example
+	/// ```rust
+	/// use snowboard::{response, Method, Server};
+	///
+	/// let server = Server::new("localhost:8080")
+	///     .expect("failed to start server")
+	///     .route(Method::GET, "/users/:id", |_, params| {
+	///         response!(ok, format!("User {}", params["id"]))
+	///     });
+	///
+	/// // server.run(|_| response!(not_found));
+	/// ```
+	pub fn route<T: ResponseLike>(
+		mut self,
+		method: Method,
+		pattern: &str,
+		handler: impl Fn(Request, Params) -> T + Send + Sync + 'static,
+	) -> Self {
+		self.router = self.router.route(method, pattern, handler);
+		self
+	}
+
+	/// Sets the routes tried before the handler, replacing any added with [`Server::route`].
+	pub fn with_router(mut self, router: Router) -> Self {
+		self.router = router;
+		self
+	}
+
+	/// Replaces empty `404 Not Found` responses returned by the handler
+	/// with the response of `handler`, to customize them in a single place.
+	///
+	/// The request is cloned before calling the main handler to be able to do this.
+	pub fn with_not_found_handler(
+		mut self,
+		handler: impl Fn(Request) -> Response + Send + Sync + 'static,
+	) -> Self {
+		self.not_found_handler = Some(Arc::new(handler));
+		self
+	}
+
+	/// Replaces empty `405 Method Not Allowed` responses returned by the handler
+	/// with the response of `handler`. The `Allow` header is kept if `handler` doesn't set it.
+	/// See [`Server::with_not_found_handler`].
+	pub fn with_method_not_allowed_handler(
+		mut self,
+		handler: impl Fn(Request) -> Response + Send + Sync + 'static,
+	) -> Self {
+		self.method_not_allowed_handler = Some(Arc::new(handler));
+		self
+	}
+
+	/// Answers requests to `path` (like `/healthz`) with an empty `200 Ok`
+	/// without calling the handler, so liveness probes don't reach the application.
+	pub fn with_health_check(mut self, path: &'static str) -> Self {
+		self.health_check = Some(path);
+		self
+	}
+
+	/// Answers requests to `path` (like `/metrics`) with metrics of the server in the
+	/// Prometheus text format, without calling the handler: the amount of requests
+	/// answered (`http_requests_total`), of responses by status code
+	/// (`http_responses_by_status`) and of connections being handled (`http_active_connections`).
+	pub fn with_metrics_endpoint(mut self, path: &'static str) -> Self {
+		self.metrics_endpoint = Some(path);
+		self
+	}
+
+	/// Stops the server when a handler panics, instead of only dropping that connection.
+	/// [`Server::checked_run`] returns an error, and [`Server::run`] exits the process.
+	/// Disabled by default.
+	pub fn with_abort_on_panic(mut self, abort: bool) -> Self {
+		self.abort_on_panic = abort;
+		self
+	}
+
 	/// Get the address the server is listening on.
 	#[inline]
 	pub fn addr(&self) -> io::Result<SocketAddr> {
@@ -136,70 +760,270 @@ impl Server {
 		self
 	}
 
+	/// Hands connections over to `handler` when a request asks to switch protocols
+	/// (with an `Upgrade` header) or to open a tunnel (with the `CONNECT` method),
+	/// giving it the request and the raw stream to use whatever protocol it wants.
+	///
+	/// The handler must respond itself, usually with `101 Switching Protocols` (or
+	/// `200 Ok` for tunnels), and the connection is closed when it returns. It runs
+	/// before middleware and the request handler, but after the WebSocket handler,
+	/// see [`Server::on_websocket`].
+	///
+	/// # Example
+	/// ```rust
+	/// use snowboard::{headers, response, Server};
+	/// use std::io::{Read, Write};
+	///
+	/// let server = Server::new("localhost:8080")
+	///     .expect("failed to start server")
+	///     .on_upgrade(|req, mut stream| {
+	///         let handshake = headers! { "Upgrade" => "echo", "Connection" => "Upgrade" };
+	///         if response!(switching_protocols, [], handshake).send_to(&mut stream).is_err() {
+	///             return;
+	///         }
+	///
+	///         // Echo every byte received.
+	///         let mut buffer = [0; 1024];
+	///         while let Ok(read @ 1..) = stream.read(&mut buffer) {
+	///             let _ = stream.write_all(&buffer[..read]);
+	///         }
+	///     });
+	/// ```
+	pub fn on_upgrade(mut self, handler: impl Fn(Request, Stream) + Send + Sync + 'static) -> Self {
+		self.upgrade_handler = Some(Arc::new(handler));
+		self
+	}
+
 	/// Runs the server synchronously using multiple threads.
+	///
+	/// If a handler panics and [`Server::with_abort_on_panic`] is set, the process exits.
 	pub fn run<T: ResponseLike>(
 		self,
 		handler: impl Fn(Request) -> T + Send + 'static + Clone,
 	) -> ! {
-		#[cfg(feature = "websocket")]
-		let ws_handler = self.ws_handler.clone();
+		// This only returns if a handler panicked.
+		if let Err(e) = self.checked_run(handler) {
+			eprintln!("Server stopped: {:#?}", e);
+		}
+
+		std::process::exit(1)
+	}
+
+	/// Runs the server like [`Server::checked_run`], calling `on_bind` with the address
+	/// the server is bound to before accepting any connection. Useful to know which
+	/// port was picked when binding to port 0, or to register the server somewhere.
+	pub fn checked_run_with<T: ResponseLike>(
+		self,
+		handler: impl Fn(Request) -> T + Send + 'static + Clone,
+		on_bind: impl FnOnce(SocketAddr),
+	) -> crate::Result {
+		on_bind(self.addr()?);
+		self.checked_run(handler)
+	}
 
-		let should_insert = self.insert_default_headers;
+	/// Runs the server synchronously using multiple threads, like [`Server::run`].
+	///
+	/// Returns an error if a handler panics and [`Server::with_abort_on_panic`]
+	/// is set. Otherwise, it never returns.
+	pub fn checked_run<T: ResponseLike>(
+		mut self,
+		handler: impl Fn(Request) -> T + Send + 'static + Clone,
+	) -> crate::Result {
+		let settings = Arc::new(self.settings());
+		let pool = self.handler_pool.map(start_handler_pool);
+		let panicked = Arc::new(AtomicBool::new(false));
+
+		let router = std::mem::take(&mut self.router);
+		let handler = move |request: Request| {
+			if router.is_empty() {
+				return handler(request).to_response();
+			}
+
+			match router.dispatch(request) {
+				Ok(response) => response,
+				Err(request) => handler(request).to_response(),
+			}
+		};
+
+		// The accept loop has to wake up once in a while to notice panics.
+		if self.abort_on_panic && self.accept_timeout.is_none() {
+			self.accept_timeout = Some(STOP_CHECK_INTERVAL);
+		}
+
+		loop {
+			if panicked.load(Ordering::SeqCst) {
+				return Err(io::Error::new(io::ErrorKind::Other, "a handler panicked"));
+			}
+
+			let permit = self.acquire_connection();
+			let (stream, request) = match self.try_accept() {
+				Ok(accepted) => accepted,
+				Err(e) => {
+					report_accept_error(&e);
+					continue;
+				}
+			};
 
-		// Needed for avoiding warning when compiling without the websocket feature.
-		#[cfg_attr(not(feature = "websocket"), allow(unused_mut))]
-		for (mut stream, mut request) in self {
 			let handler = handler.clone();
+			let settings = Arc::clone(&settings);
+			let panicked = Arc::clone(&panicked);
+			let abort_on_panic = self.abort_on_panic;
 
-			std::thread::spawn(move || {
-				#[cfg(feature = "websocket")]
-				if maybe_websocket(ws_handler, &mut stream, &mut request) {
-					return Ok(());
-				};
+			let job = move || {
+				let _permit = permit;
+				let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+					settings.handle_connection(stream, request, handler)
+				}));
 
-				handler(request)
-					.to_response()
-					.maybe_add_defaults(should_insert)
-					.send_to(&mut stream)
-			});
+				result.unwrap_or_else(|_| {
+					if abort_on_panic {
+						panicked.store(true, Ordering::SeqCst);
+					}
+
+					Err(io::Error::new(io::ErrorKind::Other, "handler panicked"))
+				})
+			};
+
+			match &pool {
+				Some(sender) => {
+					// Workers never stop, so sending can't fail.
+					let _ = sender.send(Box::new(job));
+				}
+				None => {
+					std::thread::spawn(job);
+				}
+			}
 		}
+	}
 
-		unreachable!("Server::run() should never return")
+	/// Runs the server synchronously using a handler that can be replaced
+	/// at runtime. See [`ReloadableHandler`].
+	pub fn run_reloadable(self, handler: ReloadableHandler) -> ! {
+		self.run(move |request| handler.call(request))
 	}
 
 	/// Runs the server asynchronously using multiple threads.
 	#[cfg(feature = "async")]
-	pub fn run_async<F, T, R>(self, handler: F) -> !
+	pub fn run_async<F, T, R>(mut self, handler: F) -> !
 	where
 		F: Fn(Request) -> R + Send + 'static + Clone,
 		R: Future<Output = T> + Send + 'static,
 		T: ResponseLike,
 	{
-		#[cfg(feature = "websocket")]
-		let ws_handler = self.ws_handler.clone();
+		let settings = Arc::new(self.settings());
+		let handler = async_handler(std::mem::take(&mut self.router), handler);
 
-		let should_insert = self.insert_default_headers;
+		loop {
+			let permit = self.acquire_connection();
+			let (stream, request) = match self.try_accept() {
+				Ok(accepted) => accepted,
+				Err(e) => {
+					report_accept_error(&e);
+					continue;
+				}
+			};
 
-		// Needed for avoiding warning when compiling without the websocket feature.
-		#[cfg_attr(not(feature = "websocket"), allow(unused_mut))]
-		for (mut stream, mut request) in self {
-			let handler = handler.clone();
+			let connection = handle_async(Arc::clone(&settings), stream, request, handler.clone());
 
 			async_std::task::spawn(async move {
-				#[cfg(feature = "websocket")]
-				if maybe_websocket(ws_handler, &mut stream, &mut request) {
-					return Ok(());
-				};
+				let result = connection.await;
+				drop(permit);
+				result
+			});
+		}
+	}
+
+	/// Runs the server asynchronously, like [`Server::run_async`], until `shutdown` completes.
+	///
+	/// Then, it stops accepting connections and returns once the ones being handled
+	/// are answered, so it can be stopped cleanly (e.g. at the end of a test).
+	#[cfg(feature = "async")]
+	pub fn run_until<F, T, R>(
+		mut self,
+		handler: F,
+		shutdown: impl Future<Output = ()> + Send + 'static,
+	) -> crate::Result
+	where
+		F: Fn(Request) -> R + Send + 'static + Clone,
+		R: Future<Output = T> + Send + 'static,
+		T: ResponseLike,
+	{
+		let settings = Arc::new(self.settings());
+		let handler = async_handler(std::mem::take(&mut self.router), handler);
+
+		let stopped = Arc::new(AtomicBool::new(false));
+		let stop = Arc::clone(&stopped);
+		async_std::task::spawn(async move {
+			shutdown.await;
+			stop.store(true, Ordering::SeqCst);
+		});
+
+		// The accept loop has to wake up once in a while to notice the shutdown.
+		if self.accept_timeout.is_none() {
+			self.accept_timeout = Some(STOP_CHECK_INTERVAL);
+		}
 
-				handler(request)
-					.await
-					.to_response()
-					.maybe_add_defaults(should_insert)
-					.send_to(&mut stream)
+		// Every connection holds a sender, so receiving fails once all of them are done.
+		let (in_flight, done) = mpsc::channel::<()>();
+
+		while !stopped.load(Ordering::SeqCst) {
+			let permit = self.acquire_connection();
+			let (stream, request) = match self.try_accept() {
+				Ok(accepted) => accepted,
+				Err(e) => {
+					report_accept_error(&e);
+					continue;
+				}
+			};
+
+			let in_flight = in_flight.clone();
+			let connection = handle_async(Arc::clone(&settings), stream, request, handler.clone());
+
+			async_std::task::spawn(async move {
+				let result = connection.await;
+				drop((permit, in_flight));
+				result
 			});
 		}
 
-		unreachable!("Server::run() should never return")
+		drop(in_flight);
+		let _ = done.recv();
+
+		Ok(())
+	}
+
+	/// Gets the settings needed to handle connections.
+	fn settings(&self) -> Settings {
+		Settings {
+			buffer_size: self.buffer_size,
+			insert_default_headers: self.insert_default_headers,
+			method_override: self.method_override,
+			request_timeout: self.request_timeout,
+			server_options: self.server_options.clone(),
+			cors: self.cors.clone(),
+			keep_alive: self.keep_alive,
+			close_grace: self.close_grace,
+			header_timeout: self.header_timeout,
+			max_head_size: self.max_head_size,
+			health_check: self.health_check,
+			metrics: self
+				.metrics_endpoint
+				.map(|path| (path, Arc::new(ServerStats::default()))),
+			max_connection_bytes: self.max_connection_bytes,
+			response_mapper: self.response_mapper.clone(),
+			middleware: self.middleware.clone(),
+			upgrade_handler: self.upgrade_handler.clone(),
+			early_hints: self.early_hints.clone(),
+			after_middleware: self.after_middleware.clone(),
+			not_found_handler: self.not_found_handler.clone(),
+			method_not_allowed_handler: self.method_not_allowed_handler.clone(),
+			#[cfg(feature = "compression")]
+			compression: self.compression.clone(),
+			#[cfg(feature = "tracing")]
+			tracing: self.tracing,
+			#[cfg(feature = "websocket")]
+			ws_handler: self.ws_handler,
+		}
 	}
 }
 
@@ -207,7 +1031,9 @@ impl Server {
 
 impl Server {
 	/// Try to accept a new incoming request safely.
-	/// Returns an error if the request could not be read, is empty or invalid.
+	/// Returns an error if the request could not be read, is empty or invalid,
+	/// or an `io::ErrorKind::TimedOut` error if no connection arrives before the
+	/// accept timeout (see [`Server::with_accept_timeout`]).
 	/// The request will be read into a buffer and parsed into a `Request` instance.
 	/// The buffer size can be changed with `Server::set_buffer_size()`.
 	///
@@ -235,7 +1061,8 @@ impl Server {
 	/// Returns a tuple containing the stream and Client request on success otherwise returns an io
 	/// error on failure.
 	fn try_accept_inner(&self) -> io::Result<(Stream, Request)> {
-		let (stream, ip) = self.acceptor.accept()?;
+		let (stream, ip) = self.accept_tcp()?;
+		stream.set_read_timeout(self.stream_read_timeout())?;
 		self.handle_request(stream, ip)
 	}
 
@@ -246,7 +1073,8 @@ impl Server {
 		// different implementations (even externally) for `TlsAcceptor`, so the only
 		// safe way is this.
 
-		let (mut tcp_stream, ip) = self.acceptor.accept()?;
+		let (mut tcp_stream, ip) = self.accept_tcp()?;
+		tcp_stream.set_read_timeout(self.stream_read_timeout())?;
 		let mut buffer = [0; 2];
 		tcp_stream.peek(&mut buffer)?;
 
@@ -266,45 +1094,80 @@ impl Server {
 		}
 	}
 
-	/// A helper function which handles request by checking whether the request has an appropriate
-	/// buffer size by checking if it is too large or zero (in other words empty response). Also it
-	/// checks whether the request contains a valid input.
-	///
-	/// # Arguments
-	///
-	/// * `stream` - It takes the stream implementing read and write as an argument.
-	/// * `ip` - It takes the ip address as a SocketAddr type.
-	///
-	/// # Error
-	///
-	/// Returns a tuple containing stream implementing write and read traits and Request struct on
-	/// success otherwise returns an io error on failure.
-	fn handle_request<T: io::Write + io::Read>(
-		&self,
-		mut stream: T,
-		ip: SocketAddr,
-	) -> io::Result<(T, Request)> {
-		let mut buffer: Vec<u8> = vec![0; self.buffer_size];
-		let payload_size = stream.read(&mut buffer)?;
+	/// Waits until a new connection can be handled, if the amount of connections is limited.
+	fn acquire_connection(&self) -> Option<ConnectionPermit> {
+		self.connection_limit.as_ref().map(ConnectionLimit::acquire)
+	}
 
-		if payload_size > self.buffer_size {
-			crate::response!(payload_too_large).send_to(&mut stream)?;
-			return Err(io::Error::new(
-				io::ErrorKind::InvalidInput,
-				"Payload too large",
-			));
+	/// Gets the read timeout of accepted streams: the shortest of the read
+	/// and request timeouts.
+	fn stream_read_timeout(&self) -> Option<Duration> {
+		match (self.read_timeout, self.request_timeout) {
+			(Some(read), Some(request)) => Some(read.min(request)),
+			(read, request) => read.or(request),
 		}
+	}
 
-		if payload_size == 0 {
-			crate::response!(bad_request).send_to(&mut stream)?;
-			return Err(io::Error::new(io::ErrorKind::InvalidInput, "Empty request"));
-		}
+	/// Accepts a TCP connection, waiting at most the accept timeout if it's set.
+	fn accept_tcp(&self) -> io::Result<(TcpStream, SocketAddr)> {
+		let timeout = match self.accept_timeout {
+			Some(timeout) => timeout,
+			None => return self.acceptor.accept(),
+		};
+
+		let start = Instant::now();
+		self.acceptor.set_nonblocking(true)?;
+
+		let accepted = loop {
+			match self.acceptor.accept() {
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+					let elapsed = start.elapsed();
+					if elapsed >= timeout {
+						break Err(io::Error::from(io::ErrorKind::TimedOut));
+					}
 
-		let req = match Request::new(&buffer[..payload_size], ip) {
-			Some(req) => req,
-			None => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+					std::thread::sleep(ACCEPT_POLL_INTERVAL.min(timeout - elapsed));
+				}
+				accepted => break accepted,
+			}
 		};
 
+		self.acceptor.set_nonblocking(false)?;
+
+		// Accepted streams might inherit the non-blocking mode on some platforms.
+		let (stream, ip) = accepted?;
+		stream.set_nonblocking(false)?;
+		Ok((stream, ip))
+	}
+
+	/// A helper function which handles request by checking whether the request has an appropriate
+	/// buffer size by checking if it is too large or zero (in other words empty response). Also it
+	/// checks whether the request contains a valid input.
+	///
+	/// # Arguments
+	///
+	/// * `stream` - It takes the connection stream as an argument.
+	/// * `ip` - It takes the ip address as a SocketAddr type.
+	///
+	/// # Error
+	///
+	/// Returns a tuple containing the stream and Request struct on
+	/// success otherwise returns an io error on failure.
+	fn handle_request(&self, mut stream: Stream, ip: SocketAddr) -> io::Result<(Stream, Request)> {
+		let mut req = read_request(
+			&mut stream,
+			ip,
+			self.buffer_size,
+			self.method_override,
+			self.header_timeout,
+			self.max_head_size,
+		)?;
+
+		req.connection_id = self
+			.connection_ids
+			.as_ref()
+			.map(|ids| ids.fetch_add(1, Ordering::Relaxed));
+
 		Ok((stream, req))
 	}
 
@@ -335,11 +1198,17 @@ impl Server {
 
 		let path = String::from_utf8_lossy(&path).to_string();
 
+		let authority = match &self.tls_redirect_target {
+			Some((host, 443)) => host.clone(),
+			Some((host, port)) => format!("{}:{}", host, port),
+			None => self.pretty_addr().unwrap_or_default(),
+		};
+
 		crate::response!(
 			moved_permanently,
 			[],
 			crate::headers! {
-				"Location" => format!("https://{}{}", self.pretty_addr().unwrap_or_default(), path),
+				"Location" => format!("https://{}{}", authority, path),
 				"Connection" => "keep-alive",
 				"Content-Length" => 0
 			}
@@ -350,25 +1219,1048 @@ impl Server {
 	}
 }
 
-impl Iterator for Server {
-	type Item = (Stream, Request);
+/// A shared function transforming responses.
+type ResponseMapper = Arc<dyn Fn(Response) -> Response + Send + Sync>;
 
-	fn next(&mut self) -> Option<Self::Item> {
-		match self.try_accept() {
-			Ok(r) => Some(r),
-			// TLS errors, parse requests and cancelled connections are ignored.
+/// A shared function run before the handler, possibly answering the request.
+type Middleware = Arc<dyn Fn(&mut Request) -> Option<Response> + Send + Sync>;
+
+/// A shared function run on responses of the handler.
+type AfterMiddleware = Arc<dyn Fn(&mut Response) + Send + Sync>;
+
+/// A shared function taking over upgraded connections.
+type UpgradeHandler = Arc<dyn Fn(Request, Stream) + Send + Sync>;
+
+/// A shared function giving the `Link` values sent as early hints for a request.
+type EarlyHints = Arc<dyn Fn(&Request) -> Vec<String> + Send + Sync>;
+
+/// A shared handler function returning a response.
+type SharedHandler = Arc<dyn Fn(Request) -> Response + Send + Sync>;
+
+/// A request handler that can be replaced while the server is running.
+/// Clones share the same handler, so one can be kept to replace it later.
+///
+/// # Example
+/// ```rust
+/// use snowboard::{ReloadableHandler, Server};
+///
+/// let handler = ReloadableHandler::new(|_| "old");
+/// let server = Server::new("localhost:8080").expect("failed to start server");
+///
+/// let handle = handler.clone();
+/// std::thread::spawn(move || server.run_reloadable(handle));
+///
+/// // Requests received from now on will get "new".
+/// handler.set_handler(|_| "new");
+/// ```
+#[derive(Clone)]
+pub struct ReloadableHandler {
+	/// The current handler.
+	inner: Arc<RwLock<SharedHandler>>,
+}
+
+impl ReloadableHandler {
+	/// Creates a new reloadable handler.
+	pub fn new<T: ResponseLike>(handler: impl Fn(Request) -> T + Send + Sync + 'static) -> Self {
+		Self {
+			inner: Arc::new(RwLock::new(Self::share(handler))),
+		}
+	}
+
+	/// Replaces the handler. Requests already being handled finish
+	/// with the old one, new requests use the new one.
+	pub fn set_handler<T: ResponseLike>(
+		&self,
+		handler: impl Fn(Request) -> T + Send + Sync + 'static,
+	) {
+		let mut current = self.inner.write().unwrap_or_else(|e| e.into_inner());
+		*current = Self::share(handler);
+	}
+
+	/// Handles a request using the current handler.
+	pub fn call(&self, request: Request) -> Response {
+		// The lock is released before calling, so replacing isn't blocked by slow handlers.
+		let handler = Arc::clone(&self.inner.read().unwrap_or_else(|e| e.into_inner()));
+		handler(request)
+	}
+
+	/// Converts any handler into a shared one returning a `Response`.
+	fn share<T: ResponseLike>(
+		handler: impl Fn(Request) -> T + Send + Sync + 'static,
+	) -> SharedHandler {
+		Arc::new(move |request| handler(request).to_response())
+	}
+}
+
+/// A limit of connections handled at once by a server. See [`Server::with_max_connections`].
+/// Clones share the same limit, so one can be kept to monitor it.
+///
+/// # Example
+/// ```rust
+/// use snowboard::Server;
+///
+/// let server = Server::new("localhost:8080")
+///     .expect("failed to start server")
+///     .with_max_connections(100);
+///
+/// let limit = server.connection_limit().unwrap();
+/// assert_eq!(limit.in_use(), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectionLimit {
+	/// Maximum amount of connections.
+	max: usize,
+	/// Amount of connections in use, and a condition notified when one is closed.
+	in_use: Arc<(Mutex<usize>, Condvar)>,
+}
+
+impl ConnectionLimit {
+	/// Creates a limit of `max` connections.
+	fn new(max: usize) -> Self {
+		Self {
+			max,
+			in_use: Arc::new((Mutex::new(0), Condvar::new())),
+		}
+	}
+
+	/// Gets the maximum amount of connections.
+	pub fn max(&self) -> usize {
+		self.max
+	}
+
+	/// Gets the amount of connections being handled.
+	pub fn in_use(&self) -> usize {
+		*self.in_use.0.lock().unwrap_or_else(|e| e.into_inner())
+	}
+
+	/// Waits until there's a connection available, taking it until the permit is dropped.
+	fn acquire(&self) -> ConnectionPermit {
+		let (count, released) = &*self.in_use;
+		let mut count = count.lock().unwrap_or_else(|e| e.into_inner());
+
+		while *count >= self.max {
+			count = released.wait(count).unwrap_or_else(|e| e.into_inner());
+		}
+
+		*count += 1;
+		ConnectionPermit(self.clone())
+	}
+}
+
+/// A connection taken from a [`ConnectionLimit`], given back when dropped.
+struct ConnectionPermit(ConnectionLimit);
+
+impl Drop for ConnectionPermit {
+	fn drop(&mut self) {
+		let (count, released) = &*self.0.in_use;
+		*count.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+		released.notify_one();
+	}
+}
+
+/// Answers with a `400 Bad Request` for a body the client stopped sending
+/// before it was complete, returning the error that closes the connection.
+fn incomplete_body(stream: &mut Stream) -> io::Error {
+	// The client might not be listening anymore, so failing to answer is fine.
+	let _ = crate::response!(bad_request, "Incomplete body").send_to(stream);
+	io::Error::new(io::ErrorKind::UnexpectedEof, "Incomplete body")
+}
+
+/// Reads and parses a request from the stream. See [`Server::handle_request`].
+fn read_request(
+	stream: &mut Stream,
+	ip: SocketAddr,
+	buffer_size: usize,
+	method_override: bool,
+	header_timeout: Option<Duration>,
+	max_head_size: Option<usize>,
+) -> io::Result<Request> {
+	let mut buffer: Vec<u8> = vec![0; buffer_size];
+
+	let mut payload_size = match (header_timeout, max_head_size) {
+		(None, None) => stream.read(&mut buffer)?,
+		_ => read_head(stream, &mut buffer, header_timeout, max_head_size)?,
+	};
+
+	if payload_size > buffer_size {
+		crate::response!(payload_too_large).send_to(stream)?;
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"Payload too large",
+		));
+	}
+
+	if payload_size == 0 {
+		crate::response!(bad_request).send_to(stream)?;
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "Empty request"));
+	}
+
+	if buffer[..payload_size].starts_with(HTTP2_PREFACE) {
+		crate::response!(
+			http_version_not_supported,
+			"HTTP/2 is not supported, use HTTP/1.1 instead."
+		)
+		.send_to(stream)?;
+
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"HTTP/2 is not supported",
+		));
+	}
+
+	// The body might not arrive in the same read as the head.
+	if let Some(total) = declared_size(&buffer[..payload_size]) {
+		if total > buffer_size {
+			crate::response!(payload_too_large).send_to(stream)?;
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"Payload too large",
+			));
+		}
+
+		// Clients sending `Expect: 100-continue` wait for this before sending the body.
+		if payload_size < total
+			&& head_value(&buffer[..payload_size], "Expect")
+				.map_or(false, |value| value.eq_ignore_ascii_case("100-continue"))
+		{
+			crate::response!(continue_).send_to(stream)?;
+		}
+
+		while payload_size < total {
+			match stream.read(&mut buffer[payload_size..total])? {
+				0 => return Err(incomplete_body(stream)),
+				read => payload_size += read,
+			}
+		}
+	} else if let Some(body_start) = chunked_body_start(&buffer[..payload_size]) {
+		// Chunked bodies end with an empty chunk, followed by the trailers.
+		loop {
+			match decode_chunked(&buffer[body_start..payload_size]) {
+				Ok(Some(_)) => break,
+				Ok(None) => {}
+				Err(_) => {
+					crate::response!(bad_request, "Malformed chunked body").send_to(stream)?;
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidInput,
+						"Malformed chunked body",
+					));
+				}
+			}
+
+			if payload_size == buffer_size {
+				crate::response!(payload_too_large).send_to(stream)?;
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidInput,
+					"Payload too large",
+				));
+			}
+
+			match stream.read(&mut buffer[payload_size..])? {
+				0 => return Err(incomplete_body(stream)),
+				read => payload_size += read,
+			}
+		}
+	}
+
+	let mut req = match Request::new(&buffer[..payload_size], ip) {
+		Some(req) => req,
+		None => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+	};
+
+	if method_override {
+		if let Some(method) = req.method_override() {
+			req.method = method;
+		}
+	}
+
+	Ok(req)
+}
+
+/// Gets the full size of a request (head and body) using its `Content-Length` header.
+/// Returns `None` if the head is incomplete or there's no valid `Content-Length`.
+fn declared_size(bytes: &[u8]) -> Option<usize> {
+	let head_end = bytes.windows(4).position(|window| window == b"\r\n\r\n")? + 4;
+	let length = head_value(bytes, "Content-Length")?.parse::<usize>().ok()?;
+
+	head_end.checked_add(length)
+}
+
+/// Gets where the body of a request starts if it's chunked (and has no `Content-Length`).
+fn chunked_body_start(bytes: &[u8]) -> Option<usize> {
+	let head_end = bytes.windows(4).position(|window| window == b"\r\n\r\n")? + 4;
+
+	if is_chunked(&head_value(bytes, "Transfer-Encoding")?) {
+		Some(head_end)
+	} else {
+		None
+	}
+}
+
+/// Gets the value of a header from the start of a request, before it's parsed.
+/// Returns `None` if the head is incomplete or the header is missing.
+fn head_value(bytes: &[u8], key: &str) -> Option<String> {
+	let head_end = bytes.windows(4).position(|window| window == b"\r\n\r\n")?;
+	let head = String::from_utf8_lossy(&bytes[..head_end]);
+
+	head.lines().skip(1).find_map(|line| {
+		let (name, value) = line.split_once(':')?;
+
+		if name.trim().eq_ignore_ascii_case(key) {
+			Some(value.trim().to_string())
+		} else {
+			None
+		}
+	})
+}
+
+/// Reads into `buffer` until the whole head of a request (up to `\r\n\r\n`) arrives
+/// or the buffer is full. Returns the amount of bytes read.
+///
+/// Responds `408 Request Timeout` if it takes longer than `timeout`, and `431 Request
+/// Header Fields Too Large` (or `414 URI Too Long`) if the head is longer than `max_size`.
+fn read_head(
+	stream: &mut Stream,
+	buffer: &mut [u8],
+	timeout: Option<Duration>,
+	max_size: Option<usize>,
+) -> io::Result<usize> {
+	let deadline = timeout.map(|timeout| Instant::now() + timeout);
+	let previous_timeout = tcp_stream(stream).read_timeout()?;
+
+	// Enough to find the terminator right after a head of `max_size` bytes.
+	let limit = max_size.map_or(buffer.len(), |max| max.saturating_add(4).min(buffer.len()));
+
+	let mut len = 0;
+
+	let result = loop {
+		if let Some(deadline) = deadline {
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining == Duration::ZERO {
+				break Err(io::Error::from(io::ErrorKind::TimedOut));
+			}
+
+			tcp_stream(stream).set_read_timeout(Some(remaining))?;
+		}
+
+		match stream.read(&mut buffer[len..limit]) {
+			Ok(0) => break Ok(len),
+			Ok(read) => {
+				// The terminator might be split between reads.
+				let search_start = len.saturating_sub(3);
+				len += read;
+
+				let head_end = buffer[search_start..len]
+					.windows(4)
+					.position(|window| window == b"\r\n\r\n")
+					.map(|position| search_start + position);
+
+				let too_large = match (head_end, max_size) {
+					(Some(end), Some(max)) => end > max,
+					(None, Some(max)) => len >= max.saturating_add(4),
+					(_, None) => false,
+				};
+
+				if too_large {
+					break Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						"Request head too large",
+					));
+				}
+
+				if head_end.is_some() || len == buffer.len() {
+					break Ok(len);
+				}
+			}
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
 			Err(e)
-				if e.kind() == io::ErrorKind::ConnectionAborted
-					|| e.kind() == io::ErrorKind::ConnectionReset
-					|| e.kind() == io::ErrorKind::InvalidInput =>
+				if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
 			{
-				self.next()
+				break Err(io::Error::from(io::ErrorKind::TimedOut));
+			}
+			Err(e) => break Err(e),
+		}
+	};
+
+	if deadline.is_some() {
+		tcp_stream(stream).set_read_timeout(previous_timeout)?;
+	}
+
+	match &result {
+		Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+			crate::response!(request_timeout).send_to(stream)?;
+		}
+		Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+			let request_line = buffer[..len]
+				.windows(2)
+				.position(|window| window == b"\r\n")
+				.unwrap_or(len);
+
+			if max_size.map_or(false, |max| request_line > max) {
+				crate::response!(uri_too_long).send_to(stream)?;
+			} else {
+				crate::response!(request_header_fields_too_large).send_to(stream)?;
+			}
+		}
+		_ => {}
+	}
+
+	result
+}
+
+/// Gets the TCP stream behind a connection stream.
+#[cfg(not(feature = "tls"))]
+fn tcp_stream(stream: &Stream) -> &TcpStream {
+	stream
+}
+
+/// Gets the TCP stream behind a connection stream.
+#[cfg(feature = "tls")]
+fn tcp_stream(stream: &Stream) -> &TcpStream {
+	stream.get_ref()
+}
+
+/// Settings needed to handle requests, shared between every connection.
+struct Settings {
+	/// Buffer size used to read further requests in a connection.
+	buffer_size: usize,
+	/// Whether default headers are added to responses.
+	insert_default_headers: bool,
+	/// Whether POST requests can override their method.
+	method_override: bool,
+	/// Maximum time handlers can take.
+	request_timeout: Option<Duration>,
+	/// `Allow` header sent for `OPTIONS *` requests.
+	server_options: Option<Arc<str>>,
+	/// CORS configuration.
+	cors: Option<Arc<Cors>>,
+	/// Idle timeout and maximum amount of requests of kept-alive connections.
+	keep_alive: Option<(Duration, usize)>,
+	/// Time idle kept-alive connections stay open after their timeout.
+	close_grace: Option<Duration>,
+	/// Maximum time to receive the head of a request.
+	header_timeout: Option<Duration>,
+	/// Maximum size of the head of a request.
+	max_head_size: Option<usize>,
+	/// Path answered with `200 Ok` for health checks.
+	health_check: Option<&'static str>,
+	/// Path answered with the metrics, and the metrics themselves.
+	metrics: Option<(&'static str, Arc<ServerStats>)>,
+	/// Maximum amount of bytes read from a single connection.
+	max_connection_bytes: Option<usize>,
+	/// Function applied to every response before sending it.
+	response_mapper: Option<ResponseMapper>,
+	/// Functions run before the handler, in order.
+	middleware: Vec<Middleware>,
+	/// Function taking over upgraded connections.
+	upgrade_handler: Option<UpgradeHandler>,
+	/// Function giving the links sent as early hints.
+	early_hints: Option<EarlyHints>,
+	/// Functions run on the responses of the handler, in order.
+	after_middleware: Vec<AfterMiddleware>,
+	/// Handler used for empty `404 Not Found` responses.
+	not_found_handler: Option<SharedHandler>,
+	/// Handler used for empty `405 Method Not Allowed` responses.
+	method_not_allowed_handler: Option<SharedHandler>,
+	/// Encodings responses can be compressed with, by priority.
+	#[cfg(feature = "compression")]
+	compression: Option<Arc<[Encoding]>>,
+	/// Whether requests and connections are traced.
+	#[cfg(feature = "tracing")]
+	tracing: bool,
+	/// WebSocket path and handler.
+	#[cfg(feature = "websocket")]
+	ws_handler: Option<(&'static str, fn(WebSocket<&mut Stream>))>,
+}
+
+impl Settings {
+	/// Gets the upgrade handler if the request asks to switch protocols or open a tunnel.
+	/// See [`Server::on_upgrade`].
+	fn upgrade_handler(&self, request: &Request) -> Option<UpgradeHandler> {
+		let handler = self.upgrade_handler.as_ref()?;
+
+		if request.method == Method::CONNECT || request.has_header("Upgrade") {
+			Some(Arc::clone(handler))
+		} else {
+			None
+		}
+	}
+
+	/// Sends the early hints of a request, if there are any. See [`Server::with_early_hints`].
+	fn send_early_hints(&self, request: &Request, stream: &mut Stream) -> io::Result<()> {
+		let links = match &self.early_hints {
+			Some(hints) if request.version != HttpVersion::V1_0 => hints(request),
+			_ => return Ok(()),
+		};
+
+		if links.is_empty() {
+			return Ok(());
+		}
+
+		let links: Vec<&str> = links.iter().map(String::as_str).collect();
+		Response::send_early_hints(stream, &links)
+	}
+
+	/// Runs the middleware on a request, stopping at the first one that answers it.
+	fn before(&self, request: &mut Request) -> Option<Response> {
+		self.middleware
+			.iter()
+			.find_map(|middleware| middleware(request))
+	}
+
+	/// Runs the after middleware on a response of the handler.
+	fn after(&self, mut response: Response) -> Response {
+		for middleware in &self.after_middleware {
+			middleware(&mut response);
+		}
+
+		response
+	}
+
+	/// Clones the request if it might be needed by a fallback handler.
+	/// See [`Settings::fallback`].
+	fn fallback_request(&self, request: &Request) -> Option<Request> {
+		if self.not_found_handler.is_some() || self.method_not_allowed_handler.is_some() {
+			Some(request.clone())
+		} else {
+			None
+		}
+	}
+
+	/// Replaces empty `404` and `405` responses from the handler with the
+	/// responses of the configured fallback handlers, if any.
+	fn fallback(&self, request: Option<Request>, response: Response) -> Response {
+		let request = match request {
+			Some(request) if response.is_empty() => request,
+			_ => return response,
+		};
+
+		match (
+			response.status,
+			&self.not_found_handler,
+			&self.method_not_allowed_handler,
+		) {
+			(404, Some(handler), _) => handler(request),
+			(405, _, Some(handler)) => {
+				let mut fallback = handler(request);
+				let allow = response.headers.as_ref().and_then(|h| h.get("Allow"));
+				let has_allow = fallback
+					.headers
+					.as_ref()
+					.map_or(false, |h| h.contains_key("Allow"));
+
+				if let (Some(allow), false) = (allow, has_allow) {
+					fallback.set_header("Allow", allow.clone());
+				}
+
+				fallback
 			}
-			Err(e) => {
-				// Probably an important error.
-				eprintln!("Server generated error: {:#?}", e);
-				self.next() // Continue anyways. We don't want to stop the server at production.
+			_ => response,
+		}
+	}
+
+	/// Answers requests that don't need to reach the handler, like health checks,
+	/// `OPTIONS *` or CORS preflight requests.
+	fn intercept(&self, request: &Request) -> Option<Response> {
+		health_check(self.health_check, request)
+			.or_else(|| self.metrics_response(request))
+			.or_else(|| options_asterisk(&self.server_options, request))
+			.or_else(|| self.cors.as_ref().and_then(|c| c.preflight(request)))
+	}
+
+	/// Adds the configured headers to a response and compresses it before sending it.
+	/// `origin` and `accept_encoding` are the `Origin` and `Accept-Encoding` headers of the request.
+	// Needed for avoiding warning when compiling without the compression feature.
+	#[cfg_attr(not(feature = "compression"), allow(unused_variables))]
+	fn finish(
+		&self,
+		origin: Option<&str>,
+		accept_encoding: Option<&str>,
+		response: Response,
+	) -> Response {
+		let response = match &self.cors {
+			Some(cors) => cors.apply(origin, response),
+			None => response,
+		};
+
+		#[cfg(feature = "compression")]
+		let response = match (&self.compression, accept_encoding) {
+			(Some(priority), Some(accept_encoding)) => response.compress(accept_encoding, priority),
+			_ => response,
+		};
+
+		let response = response.maybe_add_defaults(self.insert_default_headers);
+
+		let response = match &self.response_mapper {
+			Some(mapper) => mapper(response),
+			None => response,
+		};
+
+		if let Some((_, stats)) = &self.metrics {
+			stats.record(response.status);
+		}
+
+		response
+	}
+
+	/// Answers a request to the metrics path, ignoring the query.
+	fn metrics_response(&self, request: &Request) -> Option<Response> {
+		let (path, stats) = self.metrics.as_ref()?;
+
+		if request_path(request) != *path {
+			return None;
+		}
+
+		Some(crate::response!(
+			ok,
+			stats.to_prometheus(),
+			crate::headers! { "Content-Type" => "text/plain; version=0.0.4" }
+		))
+	}
+
+	/// Counts a connection as active until the returned guard is dropped.
+	fn track_connection(&self) -> Option<ActiveConnection> {
+		self.metrics
+			.as_ref()
+			.map(|(_, stats)| ActiveConnection::new(Arc::clone(stats)))
+	}
+
+	/// Creates the span of a request, recording its method and path.
+	/// The status is recorded once the response is ready.
+	#[cfg(feature = "tracing")]
+	fn request_span(&self, request: &Request) -> tracing::Span {
+		if !self.tracing {
+			return tracing::Span::none();
+		}
+
+		tracing::info_span!(
+			"http.request",
+			method = %request.method,
+			path = %request_path(request),
+			status = tracing::field::Empty,
+		)
+	}
+
+	/// Logs a connection as opened, and as closed once the returned guard is dropped.
+	#[cfg(feature = "tracing")]
+	fn trace_connection(&self, request: &Request) -> Option<TracedConnection> {
+		if !self.tracing {
+			return None;
+		}
+
+		tracing::debug!(ip = %request.ip, "connection opened");
+		Some(TracedConnection(request.ip))
+	}
+
+	/// Handles every request of a connection, starting with `request`.
+	/// More requests are read from the same connection if it's kept alive.
+	// Needed for avoiding warning when compiling without the websocket feature.
+	#[cfg_attr(not(feature = "websocket"), allow(unused_mut))]
+	fn handle_connection<T: ResponseLike>(
+		&self,
+		mut stream: Stream,
+		mut request: Request,
+		handler: impl Fn(Request) -> T + Send + 'static + Clone,
+	) -> io::Result<()> {
+		let _active = self.track_connection();
+		#[cfg(feature = "tracing")]
+		let _traced = self.trace_connection(&request);
+		let mut remaining = self.keep_alive.map_or(1, |(_, max)| max.max(1));
+		let mut bytes_left = self.max_connection_bytes;
+
+		loop {
+			#[cfg(feature = "websocket")]
+			if maybe_websocket(self.ws_handler, &mut stream, &mut request) {
+				return Ok(());
+			};
+
+			if let Some(upgrade) = self.upgrade_handler(&request) {
+				upgrade(request, stream);
+				return Ok(());
+			}
+
+			if let Some(mut response) = count_connection_bytes(&mut bytes_left, &request) {
+				return response.send_to(&mut stream);
+			}
+
+			remaining -= 1;
+
+			let idle_timeout = match self.keep_alive {
+				Some((idle, _)) if remaining > 0 && request.wants_keep_alive() => Some(idle),
+				_ => None,
+			};
+
+			let ip = request.ip;
+			let connection_id = request.connection_id;
+			let origin = request.get_header("Origin").map(String::from);
+			let accept_encoding = request.get_header("Accept-Encoding").map(String::from);
+
+			#[cfg(feature = "tracing")]
+			let span = self.request_span(&request);
+			#[cfg(feature = "tracing")]
+			let entered = span.enter();
+
+			let response = match self.intercept(&request) {
+				Some(response) => response,
+				None => {
+					let response = match self.before(&mut request) {
+						Some(response) => response,
+						None => {
+							self.send_early_hints(&request, &mut stream)?;
+							let fallback_request = self.fallback_request(&request);
+
+							let response = match self.request_timeout {
+								Some(timeout) => {
+									run_with_timeout(handler.clone(), request, timeout)
+								}
+								None => handler(request).to_response(),
+							};
+
+							self.fallback(fallback_request, response)
+						}
+					};
+
+					self.after(response)
+				}
+			};
+
+			let mut response = self.finish(origin.as_deref(), accept_encoding.as_deref(), response);
+			#[cfg(feature = "tracing")]
+			span.record("status", response.status);
+
+			match idle_timeout {
+				Some(idle) => {
+					response
+						.set_header("Connection", "keep-alive".into())
+						.set_header(
+							"Keep-Alive",
+							format!("timeout={}, max={}", idle.as_secs(), remaining),
+						);
+				}
+				None if self.keep_alive.is_some() => {
+					response.set_header("Connection", "close".into());
+				}
+				None => {}
 			}
+
+			response.send_to_stream(&mut stream)?;
+			#[cfg(feature = "tracing")]
+			drop(entered);
+
+			let idle = match idle_timeout {
+				Some(idle) => idle,
+				None => return Ok(()),
+			};
+
+			// The advertised timeout is kept, but the connection lingers a bit longer.
+			let linger = idle + self.close_grace.unwrap_or(Duration::ZERO);
+			tcp_stream(&stream).set_read_timeout(Some(linger))?;
+			request = read_request(
+				&mut stream,
+				ip,
+				self.buffer_size,
+				self.method_override,
+				self.header_timeout,
+				self.max_head_size,
+			)?;
+			request.connection_id = connection_id;
+		}
+	}
+}
+
+/// Metrics of a server, served by [`Server::with_metrics_endpoint`].
+#[derive(Debug, Default)]
+struct ServerStats {
+	/// Amount of requests answered.
+	requests: AtomicU64,
+	/// Amount of responses sent, by status code.
+	responses: Mutex<BTreeMap<u16, u64>>,
+	/// Amount of connections being handled.
+	active_connections: AtomicU64,
+}
+
+impl ServerStats {
+	/// Counts a response about to be sent.
+	fn record(&self, status: u16) {
+		self.requests.fetch_add(1, Ordering::Relaxed);
+
+		if let Ok(mut responses) = self.responses.lock() {
+			*responses.entry(status).or_insert(0) += 1;
 		}
 	}
+
+	/// Formats the metrics in the Prometheus text exposition format.
+	fn to_prometheus(&self) -> String {
+		let mut text = String::from(
+			"# HELP http_requests_total Amount of requests answered.\n\
+			 # TYPE http_requests_total counter\n",
+		);
+		text += &format!(
+			"http_requests_total {}\n",
+			self.requests.load(Ordering::Relaxed)
+		);
+
+		text += "# HELP http_responses_by_status Amount of responses sent, by status code.\n\
+				 # TYPE http_responses_by_status counter\n";
+		if let Ok(responses) = self.responses.lock() {
+			for (status, count) in responses.iter() {
+				text += &format!(
+					"http_responses_by_status{{status=\"{}\"}} {}\n",
+					status, count
+				);
+			}
+		}
+
+		text += "# HELP http_active_connections Amount of connections being handled.\n\
+				 # TYPE http_active_connections gauge\n";
+		text += &format!(
+			"http_active_connections {}\n",
+			self.active_connections.load(Ordering::Relaxed)
+		);
+
+		text
+	}
+}
+
+/// A connection counted as active in [`ServerStats`] until dropped.
+struct ActiveConnection(Arc<ServerStats>);
+
+impl ActiveConnection {
+	/// Starts counting a connection as active.
+	fn new(stats: Arc<ServerStats>) -> Self {
+		stats.active_connections.fetch_add(1, Ordering::Relaxed);
+		Self(stats)
+	}
+}
+
+impl Drop for ActiveConnection {
+	fn drop(&mut self) {
+		self.0.active_connections.fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+/// A connection logged as closed when dropped. See [`Settings::trace_connection`].
+#[cfg(feature = "tracing")]
+struct TracedConnection(SocketAddr);
+
+#[cfg(feature = "tracing")]
+impl Drop for TracedConnection {
+	fn drop(&mut self) {
+		tracing::debug!(ip = %self.0, "connection closed");
+	}
+}
+
+/// Counts a request towards the limit set with [`Server::with_max_connection_bytes`],
+/// returning the `413 Payload Too Large` response closing the connection if it's exceeded.
+fn count_connection_bytes(bytes_left: &mut Option<usize>, request: &Request) -> Option<Response> {
+	let left = bytes_left.as_mut()?;
+	let size = request.bytes_received();
+
+	if size > *left {
+		return Some(crate::response!(payload_too_large).with_header("Connection", "close".into()));
+	}
+
+	*left -= size;
+	None
+}
+
+/// Gets the path of a request, without the query.
+fn request_path(request: &Request) -> &str {
+	request.url.split('?').next().unwrap_or("")
+}
+
+/// Answers a request to the health check path, ignoring the query.
+fn health_check(path: Option<&str>, request: &Request) -> Option<Response> {
+	match path {
+		Some(path) if request_path(request) == path => Some(crate::response!(ok)),
+		_ => None,
+	}
+}
+
+/// Answers an `OPTIONS *` request if `allow` is set.
+fn options_asterisk(allow: &Option<Arc<str>>, request: &Request) -> Option<Response> {
+	match allow {
+		Some(allow) if request.method == Method::OPTIONS && request.url == "*" => Some(
+			crate::response!(ok, [], crate::headers! { "Allow" => allow }),
+		),
+		_ => None,
+	}
+}
+
+/// A request to be handled by the handler pool.
+type Job = Box<dyn FnOnce() -> io::Result<()> + Send>;
+
+/// Starts `size` worker threads, returning the sender used to give them jobs.
+fn start_handler_pool(size: usize) -> mpsc::Sender<Job> {
+	let (sender, receiver) = mpsc::channel::<Job>();
+	let receiver = Arc::new(Mutex::new(receiver));
+
+	for _ in 0..size {
+		let receiver = Arc::clone(&receiver);
+
+		std::thread::spawn(move || loop {
+			let job = match receiver.lock() {
+				Ok(receiver) => receiver.recv(),
+				Err(_) => return,
+			};
+
+			match job {
+				// A panicking handler shouldn't take the worker down with it.
+				Ok(job) => {
+					let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+				}
+				Err(_) => return,
+			}
+		});
+	}
+
+	sender
+}
+
+/// A response being generated asynchronously.
+#[cfg(feature = "async")]
+type AsyncResponse = Pin<Box<dyn Future<Output = Response> + Send>>;
+
+/// Wraps an async handler, answering the requests matched by the router before calling it.
+#[cfg(feature = "async")]
+// The output isn't a type parameter, so the returned closure doesn't capture it.
+fn async_handler<F, R>(
+	router: Router,
+	handler: F,
+) -> impl Fn(Request) -> AsyncResponse + Send + Clone
+where
+	F: Fn(Request) -> R + Send + 'static + Clone,
+	R: Future + Send + 'static,
+	R::Output: ResponseLike,
+{
+	// Routes are synchronous, so their responses are wrapped in ready futures.
+	move |request: Request| -> AsyncResponse {
+		match router.dispatch(request) {
+			Ok(response) => Box::pin(async move { response }),
+			Err(request) => {
+				let response = handler(request);
+				Box::pin(async move { response.await.to_response() })
+			}
+		}
+	}
+}
+
+/// Handles a connection accepted by an async server.
+#[cfg(feature = "async")]
+// Needed for avoiding warning when compiling without the websocket feature.
+#[cfg_attr(not(feature = "websocket"), allow(unused_mut))]
+async fn handle_async(
+	settings: Arc<Settings>,
+	mut stream: Stream,
+	mut request: Request,
+	handler: impl Fn(Request) -> AsyncResponse + Send,
+) -> io::Result<()> {
+	let _active = settings.track_connection();
+	#[cfg(feature = "tracing")]
+	let _traced = settings.trace_connection(&request);
+
+	#[cfg(feature = "websocket")]
+	if maybe_websocket(settings.ws_handler, &mut stream, &mut request) {
+		return Ok(());
+	};
+
+	if let Some(upgrade) = settings.upgrade_handler(&request) {
+		upgrade(request, stream);
+		return Ok(());
+	}
+
+	// Only one request is read per connection, so it's the only one counted.
+	let mut bytes_left = settings.max_connection_bytes;
+	if let Some(mut response) = count_connection_bytes(&mut bytes_left, &request) {
+		return response.send_to(&mut stream);
+	}
+
+	let origin = request.get_header("Origin").map(String::from);
+	let accept_encoding = request.get_header("Accept-Encoding").map(String::from);
+
+	// Not entered, since the task can move between threads at every `.await`.
+	#[cfg(feature = "tracing")]
+	let span = settings.request_span(&request);
+
+	let response = match settings.intercept(&request) {
+		Some(response) => response,
+		None => match settings.before(&mut request) {
+			Some(response) => settings.after(response),
+			None => {
+				settings.send_early_hints(&request, &mut stream)?;
+				let fallback_request = settings.fallback_request(&request);
+
+				let response = match settings.request_timeout {
+					Some(timeout) => async_std::future::timeout(timeout, handler(request))
+						.await
+						.map(ResponseLike::to_response)
+						.unwrap_or_else(|_| crate::response!(service_unavailable)),
+					None => handler(request).await.to_response(),
+				};
+
+				settings.after(settings.fallback(fallback_request, response))
+			}
+		},
+	};
+
+	let mut response = settings.finish(origin.as_deref(), accept_encoding.as_deref(), response);
+	#[cfg(feature = "tracing")]
+	span.record("status", response.status);
+
+	response.send_to_stream(&mut stream)
+}
+
+/// Runs the handler in a different thread, responding with
+/// `503 Service Unavailable` if it doesn't finish before `timeout`.
+///
+/// If the handler panics, the panic is resumed in the current thread, so it's
+/// handled like any other (see [`Server::with_abort_on_panic`]).
+fn run_with_timeout<T: ResponseLike>(
+	handler: impl FnOnce(Request) -> T + Send + 'static,
+	request: Request,
+	timeout: Duration,
+) -> Response {
+	let (sender, receiver) = mpsc::channel();
+
+	let worker = std::thread::spawn(move || {
+		// The receiver is gone if the request already timed out.
+		let _ = sender.send(handler(request).to_response());
+	});
+
+	match receiver.recv_timeout(timeout) {
+		Ok(response) => response,
+		Err(mpsc::RecvTimeoutError::Timeout) => crate::response!(service_unavailable),
+		// The sender is only dropped without sending if the handler panicked.
+		Err(mpsc::RecvTimeoutError::Disconnected) => match worker.join() {
+			Err(panic) => std::panic::resume_unwind(panic),
+			Ok(()) => crate::response!(service_unavailable),
+		},
+	}
+}
+
+impl Iterator for Server {
+	type Item = (Stream, Request);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			match self.try_accept() {
+				Ok(r) => return Some(r),
+				// Continue anyways. We don't want to stop the server at production.
+				Err(e) => report_accept_error(&e),
+			}
+		}
+	}
+}
+
+/// Logs an error generated while accepting a request.
+/// TLS errors, parse requests, cancelled connections and timeouts are ignored.
+fn report_accept_error(e: &io::Error) {
+	match e.kind() {
+		io::ErrorKind::ConnectionAborted
+		| io::ErrorKind::ConnectionReset
+		| io::ErrorKind::InvalidInput
+		| io::ErrorKind::TimedOut
+		| io::ErrorKind::WouldBlock => {}
+		// Probably an important error.
+		_ => eprintln!("Server generated error: {:#?}", e),
+	}
 }
@@ -1,15 +1,38 @@
 //! A module that provides code to handle the parsing of the URL of the server.
 
-use std::{collections::HashMap, fmt::Display};
+use std::{borrow::Cow, collections::HashMap, fmt::Display, str::FromStr};
+
+/// Longest file extension considered by [`Url::is_asset`].
+const MAX_EXTENSION_LEN: usize = 8;
 
 /// A parsed URL.
 #[cfg_attr(feature = "json", derive(serde::Serialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Url<'a> {
-	/// Original path, divided by `/`
+	/// Original path, divided by `/`.
+	/// Parsed URLs keep at most [`Url::MAX_PATH_SEGMENTS`] segments.
 	pub path: Vec<&'a str>,
 	/// Search parameters, specified using `?key=value` in the URL.
+	/// If a key is repeated, only the last value is kept.
+	/// See [`Url::search_params_all`] to get every value.
+	/// Parsed URLs only read the first [`Url::MAX_SEARCH_PARAMS`] parameters.
 	pub search_params: HashMap<&'a str, &'a str>,
+	/// Raw query string, without the `?`. See [`Url::query`].
+	query: &'a str,
+	/// Whether the path and search parameters are percent-encoded, as sent in parsed URLs.
+	/// Those given to `Url::new` are plain text.
+	#[cfg_attr(feature = "json", serde(skip))]
+	encoded: bool,
+}
+
+impl Url<'_> {
+	/// Maximum amount of path segments kept when parsing a URL. Further segments
+	/// are ignored, so huge paths can't make the server allocate huge lists.
+	pub const MAX_PATH_SEGMENTS: usize = 256;
+
+	/// Maximum amount of search parameters read when parsing a URL.
+	/// Further parameters are ignored, like [`Url::MAX_PATH_SEGMENTS`].
+	pub const MAX_SEARCH_PARAMS: usize = 256;
 }
 
 impl<'a> Url<'a> {
@@ -19,6 +42,8 @@ impl<'a> Url<'a> {
 		Self {
 			path,
 			search_params,
+			query: "",
+			encoded: false,
 		}
 	}
 
@@ -28,28 +53,249 @@ impl<'a> Url<'a> {
 		self.path.get(i).copied()
 	}
 
+	/// Returns the `i` element of the path, percent-decoded.
+	/// Unlike in search parameters, `+` is kept as is, since it only means
+	/// a space in queries. Invalid escapes are kept as they are.
+	pub fn at_decoded(&self, i: usize) -> Option<Cow<'a, str>> {
+		self.at(i).map(|segment| percent_decode(segment, false))
+	}
+
 	/// Gets a copy of a search parameter.
 	pub fn search_param(&self, key: &'a str) -> Option<&'a str> {
 		self.search_params.get(key).copied()
 	}
 
+	/// Gets a search parameter, percent-decoded (`+` is decoded as a space).
+	/// Invalid escapes are kept as they are.
+	pub fn search_param_decoded(&self, key: &str) -> Option<Cow<'a, str>> {
+		let value: &'a str = self.search_params.get(key)?;
+		Some(percent_decode(value, true))
+	}
+
+	/// Gets a search parameter (percent-decoded) parsed as `T`, like a number or a `bool`.
+	/// Returns `None` if the parameter is missing or can't be parsed.
+	pub fn search_param_as<T: FromStr>(&self, key: &str) -> Option<T> {
+		self.search_param_decoded(key)?.parse().ok()
+	}
+
+	/// Gets the raw query string, without the `?`.
+	/// Empty if the URL was created using `Url::new`.
+	pub fn query(&self) -> &'a str {
+		self.query
+	}
+
+	/// Gets every value of a (possibly repeated) search parameter, in order.
+	/// Useful for array notation, like `?a[]=1&a[]=2` (use `"a[]"` as the key).
+	pub fn search_params_all(&self, key: &str) -> Vec<&'a str> {
+		self.query
+			.split('&')
+			.filter_map(|s| {
+				let (k, value) = s.split_once('=').unwrap_or((s, ""));
+
+				if k == key {
+					Some(value)
+				} else {
+					None
+				}
+			})
+			.collect()
+	}
+
 	/// Checks if a search parameter exists.
 	pub fn has_search_param(&self, key: &'a str) -> bool {
 		self.search_params.contains_key(key)
 	}
+
+	/// Checks if the URL points to a static asset, that is, the last segment of
+	/// the path has a file extension (like `/app.js`). Useful for SPA fallbacks.
+	pub fn is_asset(&self) -> bool {
+		let last = match self.path.last() {
+			Some(last) => last,
+			None => return false,
+		};
+
+		match last.rsplit_once('.') {
+			Some((stem, extension)) => {
+				!stem.is_empty()
+					&& (1..=MAX_EXTENSION_LEN).contains(&extension.len())
+					&& extension.bytes().all(|b| b.is_ascii_alphanumeric())
+			}
+			None => false,
+		}
+	}
+
+	/// Serializes the path and search parameters as `/path?key=value`, percent-encoding
+	/// every segment, key and value. Search parameters are sorted by key.
+	///
+	/// Everything is encoded exactly once: the segments and parameters of parsed URLs
+	/// are decoded first, and the ones given to `Url::new` are taken as plain text.
+	pub fn to_encoded_string(&self) -> String {
+		let mut text = String::new();
+
+		for segment in &self.path {
+			text.push('/');
+			text.push_str(&self.encode(segment, false));
+		}
+
+		if text.is_empty() {
+			text.push('/');
+		}
+
+		let mut params: Vec<_> = self.search_params.iter().collect();
+		params.sort();
+
+		for (i, (key, value)) in params.into_iter().enumerate() {
+			text.push(if i == 0 { '?' } else { '&' });
+			text.push_str(&self.encode(key, true));
+			text.push('=');
+			text.push_str(&self.encode(value, true));
+		}
+
+		text
+	}
+
+	/// Percent-encodes a segment, key or value, decoding it first if the URL was parsed.
+	/// `plus_as_space` is passed to the decoding (see [`percent_decode`]).
+	fn encode(&self, text: &str, plus_as_space: bool) -> String {
+		if self.encoded {
+			percent_encode(&percent_decode(text, plus_as_space)).into_owned()
+		} else {
+			percent_encode(text).into_owned()
+		}
+	}
+}
+
+/// Percent-decodes `text`, also converting `+` to a space if `plus_as_space` is set
+/// (as in `application/x-www-form-urlencoded` data). Invalid escapes are kept as they are.
+pub(crate) fn percent_decode(text: &str, plus_as_space: bool) -> Cow<'_, str> {
+	let needs_decoding = text.contains('%') || (plus_as_space && text.contains('+'));
+	if !needs_decoding {
+		return Cow::Borrowed(text);
+	}
+
+	let bytes = text.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+
+	while i < bytes.len() {
+		match bytes[i] {
+			b'%' => {
+				// `from_str_radix` also accepts a sign, like in `%+A`.
+				let hex = bytes
+					.get(i + 1..i + 3)
+					.filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+					.and_then(|hex| std::str::from_utf8(hex).ok())
+					.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+				match hex {
+					Some(byte) => {
+						decoded.push(byte);
+						i += 3;
+						continue;
+					}
+					None => decoded.push(b'%'),
+				}
+			}
+			b'+' if plus_as_space => decoded.push(b' '),
+			byte => decoded.push(byte),
+		}
+
+		i += 1;
+	}
+
+	Cow::Owned(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+/// Percent-encodes every byte of `text` except unreserved characters
+/// (letters, digits, `-`, `.`, `_` and `~`).
+pub(crate) fn percent_encode(text: &str) -> Cow<'_, str> {
+	let is_unreserved = |b: &u8| b.is_ascii_alphanumeric() || b"-._~".contains(b);
+
+	if text.bytes().all(|b| is_unreserved(&b)) {
+		return Cow::Borrowed(text);
+	}
+
+	let mut encoded = String::with_capacity(text.len() * 3);
+
+	for byte in text.bytes() {
+		if is_unreserved(&byte) {
+			encoded.push(byte as char);
+		} else {
+			encoded.push_str(&format!("%{:02X}", byte));
+		}
+	}
+
+	Cow::Owned(encoded)
+}
+
+/// A parsed URL that owns its data, so it can outlive the request.
+/// Use `Request::parse_url_owned` or `OwnedUrl::from(url)` to create one.
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedUrl {
+	/// Original path, divided by `/`
+	pub path: Vec<String>,
+	/// Search parameters, specified using `?key=value` in the URL.
+	pub search_params: HashMap<String, String>,
+	/// Raw query string, without the `?`. See [`OwnedUrl::query`].
+	query: String,
+}
+
+impl OwnedUrl {
+	/// Returns the `i` element of the path.
+	/// If the element does not exist, returns `None`.
+	pub fn at(&self, i: usize) -> Option<&str> {
+		self.path.get(i).map(|s| s.as_str())
+	}
+
+	/// Gets a search parameter.
+	pub fn search_param(&self, key: &str) -> Option<&str> {
+		self.search_params.get(key).map(|s| s.as_str())
+	}
+
+	/// Checks if a search parameter exists.
+	pub fn has_search_param(&self, key: &str) -> bool {
+		self.search_params.contains_key(key)
+	}
+
+	/// Gets the raw query string, without the `?`.
+	pub fn query(&self) -> &str {
+		&self.query
+	}
+}
+
+impl From<Url<'_>> for OwnedUrl {
+	fn from(url: Url<'_>) -> Self {
+		Self {
+			path: url.path.into_iter().map(String::from).collect(),
+			search_params: url
+				.search_params
+				.into_iter()
+				.map(|(key, value)| (key.to_string(), value.to_string()))
+				.collect(),
+			query: url.query.to_string(),
+		}
+	}
 }
 
 impl<'a> From<&'a str> for Url<'a> {
 	fn from(value: &'a str) -> Self {
 		let (path_part, query_part) = value.split_once('?').unwrap_or((value, ""));
-		let path: Vec<&'a str> = path_part.split('/').filter(|x| !x.is_empty()).collect();
+		let path: Vec<&'a str> = path_part
+			.split('/')
+			.filter(|x| !x.is_empty())
+			.take(Self::MAX_PATH_SEGMENTS)
+			.collect();
 
 		let mut search_params = HashMap::new();
 
 		if !query_part.is_empty() {
-			for s in query_part.split('&') {
+			for s in query_part.split('&').take(Self::MAX_SEARCH_PARAMS) {
+				// Only the first `=` splits, so `x=a=b` has the value `a=b`.
 				let (key, value) = s.split_once('=').unwrap_or((s, ""));
-				if key.is_empty() {
+
+				// Empty pairs (like in `a=1&&b=2`) are skipped, but `=value` keeps its empty key.
+				if key.is_empty() && value.is_empty() {
 					continue;
 				}
 
@@ -57,19 +303,28 @@ impl<'a> From<&'a str> for Url<'a> {
 			}
 		}
 
-		Self::new(path, search_params)
+		Self {
+			path,
+			search_params,
+			query: query_part,
+			encoded: true,
+		}
 	}
 }
 
 use std::fmt;
 
+/// Keys and values of search parameters are percent-encoded exactly once, like in
+/// [`Url::to_encoded_string`], so values containing reserved characters (like `&` or `=`)
+/// don't break the query.
 impl Display for Url<'_> {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let encode = |text: &str| self.encode(text, true);
 		let path_str = self.path.join("/");
 		let params = self
 			.search_params
 			.iter()
-			.map(|(key, value)| format!("{}={}", key, value))
+			.map(|(key, value)| format!("{}={}", encode(key), encode(value)))
 			.collect::<Vec<String>>()
 			.join("&");
 
@@ -28,9 +28,27 @@ pub enum Method {
 	UNKNOWN,
 }
 
+impl Method {
+	/// Gets the name of the method, like `"GET"`.
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Method::GET => "GET",
+			Method::POST => "POST",
+			Method::PUT => "PUT",
+			Method::DELETE => "DELETE",
+			Method::HEAD => "HEAD",
+			Method::OPTIONS => "OPTIONS",
+			Method::CONNECT => "CONNECT",
+			Method::PATCH => "PATCH",
+			Method::TRACE => "TRACE",
+			Method::UNKNOWN => "UNKNOWN",
+		}
+	}
+}
+
 impl Display for Method {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "{:?}", self)
+		f.write_str(self.as_str())
 	}
 }
 
@@ -99,6 +117,53 @@ impl From<&str> for HttpVersion {
 	}
 }
 
+/// Gets the `Content-Type` for a file extension (without the dot, in any case),
+/// or `application/octet-stream` if it's unknown. Text types include `charset=utf-8`.
+///
+/// This is the mapping used by [`crate::Response::file`].
+///
+/// # Example
+/// ```rust
+/// use snowboard::{mime_from_extension, response};
+///
+/// assert_eq!(mime_from_extension("PNG"), "image/png");
+/// assert_eq!(mime_from_extension("unknown"), "application/octet-stream");
+///
+/// let res = response!(ok, "a,b").with_content_type(mime_from_extension("csv").into());
+/// ```
+pub fn mime_from_extension(ext: &str) -> &'static str {
+	match ext.to_ascii_lowercase().as_str() {
+		"html" | "htm" => "text/html; charset=utf-8",
+		"css" => "text/css; charset=utf-8",
+		"js" | "mjs" => "text/javascript; charset=utf-8",
+		"json" => "application/json",
+		"txt" => "text/plain; charset=utf-8",
+		"csv" => "text/csv; charset=utf-8",
+		"xml" => "application/xml",
+		"png" => "image/png",
+		"jpg" | "jpeg" => "image/jpeg",
+		"gif" => "image/gif",
+		"svg" => "image/svg+xml",
+		"webp" => "image/webp",
+		"ico" => "image/x-icon",
+		"avif" => "image/avif",
+		"woff" => "font/woff",
+		"woff2" => "font/woff2",
+		"ttf" => "font/ttf",
+		"otf" => "font/otf",
+		"mp3" => "audio/mpeg",
+		"ogg" => "audio/ogg",
+		"wav" => "audio/wav",
+		"mp4" => "video/mp4",
+		"webm" => "video/webm",
+		"pdf" => "application/pdf",
+		"zip" => "application/zip",
+		"gz" => "application/gzip",
+		"wasm" => "application/wasm",
+		_ => "application/octet-stream",
+	}
+}
+
 /// Formats a socket address into something usable.
 pub fn format_addr(addr: SocketAddr) -> String {
 	match addr {
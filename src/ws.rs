@@ -29,11 +29,15 @@ impl Request {
 	/// Even though the protocol requests more headers, only the
 	/// `Sec-WebSocket-Key` and `Upgrade` headers are checked.
 	pub fn is_websocket(&self) -> bool {
-		self.headers
-			.get("Upgrade")
-			.map(|value| value == "websocket")
+		self.wants_websocket() && self.has_header("Sec-WebSocket-Key")
+	}
+
+	/// Checks if a request asks to be upgraded to a WebSocket connection,
+	/// using the `Upgrade` header, even if the handshake is not valid.
+	fn wants_websocket(&self) -> bool {
+		self.get_header("Upgrade")
+			.map(|value| value.eq_ignore_ascii_case("websocket"))
 			.unwrap_or(false)
-			&& self.headers.contains_key("Sec-WebSocket-Key")
 	}
 
 	/// Upgrades a request to a WebSocket connection.
@@ -43,7 +47,7 @@ impl Request {
 			return None;
 		}
 
-		let ws_key = self.headers.get("Sec-WebSocket-Key")?.clone();
+		let ws_key = self.get_header("Sec-WebSocket-Key")?.to_string();
 		let handshake = build_handshake(ws_key);
 
 		crate::response!(switching_protocols, [], handshake)
@@ -60,7 +64,8 @@ impl Request {
 
 /// Tries to upgrade a request to a WebSocket connection, ignoring errors.
 /// If upgrading succeeds, the WebSocket is passed to `self.ws_handler`.
-/// Does nothing if the request is not a WebSocket handshake request.
+/// Does nothing if the request doesn't ask for a WebSocket, and responds
+/// with a `400 Bad Request` if the handshake is malformed.
 #[cfg(feature = "websocket")]
 pub fn maybe_websocket<Stream: io::Write>(
 	handler: Option<(&'static str, fn(WebSocket<&mut Stream>))>,
@@ -68,10 +73,16 @@ pub fn maybe_websocket<Stream: io::Write>(
 	req: &mut Request,
 ) -> bool {
 	let handler = match handler {
-		Some((path, f)) if req.url.starts_with(path) => f,
+		Some((path, f)) if req.url.starts_with(path) && req.wants_websocket() => f,
 		_ => return false,
 	};
 
+	if !req.is_websocket() || !req.has_header("Sec-WebSocket-Version") {
+		// The response can't be sent if the connection is already gone.
+		let _ = crate::response!(bad_request).send_to(stream);
+		return true;
+	}
+
 	// Calls `handler` if `request.upgrade(..)` returns `Some(..)`.
 	req.upgrade(stream).map(handler);
 	true
@@ -1,2 +1,4 @@
 mod parsers;
 mod response;
+mod router;
+mod server;
@@ -10,7 +10,7 @@ macro_rules! map_into {
 
 use std::collections::HashMap;
 
-use snowboard::{Method, Request, Url};
+use snowboard::{HttpVersion, Method, Request, Url};
 
 #[test]
 fn parse_request() {
@@ -18,20 +18,23 @@ fn parse_request() {
 
 	let sample_ip = "127.0.0.1:8080".parse().unwrap();
 
+	let parsed = Request::new(request, sample_ip).unwrap();
+
+	assert_eq!(parsed.ip, sample_ip);
+	assert_eq!(parsed.url, "/");
+	assert_eq!(parsed.method, Method::HEAD);
+	assert_eq!(parsed.version, HttpVersion::V1_1);
+	assert_eq!(parsed.body, b"BODY, BODY, BODY\nMORE BODY\n");
 	assert_eq!(
-		Request::new(request, sample_ip).unwrap(),
-		Request {
-			ip: sample_ip,
-			url: "/".into(),
-			method: Method::HEAD,
-			body: "BODY, BODY, BODY\nMORE BODY\n".into(),
-			headers: map_into! {
-				"Host" => "localhost:8080",
-				"User-Agent" => "curl/xx",
-				"Accept" => "*/*",
-			}
+		parsed.headers,
+		map_into! {
+			"host" => "localhost:8080",
+			"user-agent" => "curl/xx",
+			"accept" => "*/*",
 		}
 	);
+	assert_eq!(parsed.connection_id(), None);
+	assert_eq!(parsed.bytes_received(), request.len());
 }
 
 #[test]
@@ -47,18 +50,12 @@ fn parse_invalid_utf8() {
 
 	let parsed = Request::new(&request, sample_ip).unwrap();
 
-	assert_eq!(
-		parsed,
-		Request {
-			ip: sample_ip,
-			url: "/".into(),
-			method: Method::GET,
-			body: vec![0x80, 0xFF, 0xC0],
-			headers: map_into! {
-				"X-A" => "B",
-			}
-		}
-	);
+	assert_eq!(parsed.ip, sample_ip);
+	assert_eq!(parsed.url, "/");
+	assert_eq!(parsed.method, Method::GET);
+	assert_eq!(parsed.body, vec![0x80, 0xFF, 0xC0]);
+	assert_eq!(parsed.headers, map_into! { "x-a" => "B" });
+	assert_eq!(parsed.bytes_received(), request.len());
 
 	// Invalid UTF-8 bytes get converted to the replacement character (�)
 	assert_eq!(parsed.text(), "���")
@@ -84,21 +81,18 @@ fn test_different_amount_of_headers() {
 
 		let mut headers = HashMap::new();
 		for _ in 0..i {
-			headers.insert("A".into(), "B".into());
+			headers.insert("a".into(), "B".into());
 		}
 
-		headers.insert("Host".into(), "localhost:8080".into());
-
-		assert_eq!(
-			parsed,
-			Request {
-				ip: sample_ip,
-				url: "/".into(),
-				method: Method::GET,
-				body: b"h".into(),
-				headers
-			}
-		);
+		headers.insert("host".into(), "localhost:8080".into());
+
+		assert_eq!(parsed.ip, sample_ip);
+		assert_eq!(parsed.url, "/");
+		assert_eq!(parsed.method, Method::GET);
+		assert_eq!(parsed.body, b"h");
+		assert_eq!(parsed.headers, headers);
+		assert_eq!(parsed.get_headers("A"), vec!["B"; i]);
+		assert_eq!(parsed.bytes_received(), request.len());
 	}
 }
 
@@ -109,51 +103,665 @@ fn parse_url() {
 	let base = "/";
 	let weird = "/?&=";
 	let no_query = "/a/b/c";
+	let parts = |url: &'static str| {
+		let url = Url::from(url);
+		let query = url.query();
+		(url.path, url.search_params, query)
+	};
 
 	assert_eq!(
-		Url::from(complex),
-		Url {
-			path: vec!["path", "to", "something"],
-			search_params: map_into! {
+		parts(complex),
+		(
+			vec!["path", "to", "something"],
+			map_into! {
 				"param1" => "value1",
 				"param2" => "value2",
 				"param3" => "value3",
 				"s" => "",
-			}
-		}
+			},
+			"param1=value1&param2=value2&param3=value3&s=&"
+		)
 	);
 
 	assert_eq!(
-		Url::from(simple),
-		Url {
-			path: vec!["a", "b"],
-			search_params: map_into! {
+		parts(simple),
+		(
+			vec!["a", "b"],
+			map_into! {
 				"c" => "d",
-			}
-		}
+			},
+			"c=d"
+		)
 	);
 
+	assert_eq!(parts(base), (vec![], HashMap::new(), ""));
+
+	assert_eq!(parts(weird), (vec![], HashMap::new(), "&="));
+
+	assert_eq!(parts(no_query), (vec!["a", "b", "c"], HashMap::new(), ""));
+}
+
+#[test]
+fn parse_url_query_edge_cases() {
+	let url = Url::from("/?x=a=b&flag&=orphan&token=a=b=c");
+
+	assert_eq!(url.search_param("x"), Some("a=b"));
+	assert_eq!(url.search_param("token"), Some("a=b=c"));
+	assert_eq!(url.search_param("flag"), Some(""));
+	assert!(url.has_search_param("flag"));
+	assert_eq!(url.search_param(""), Some("orphan"));
+	assert_eq!(url.search_params.len(), 4);
+}
+
+#[test]
+fn get_single_cookie() {
+	let request =
+		b"GET / HTTP/1.1\r\nCookie: session=abc123; theme = \"dark mode\" ;empty=\r\n\r\n";
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	let parsed = Request::new(request, sample_ip).unwrap();
+
+	assert_eq!(parsed.cookie("session"), Some("abc123"));
+	assert_eq!(parsed.cookie("theme"), Some("dark mode"));
+	assert_eq!(parsed.cookie("empty"), Some(""));
+	assert_eq!(parsed.cookie("missing"), None);
+
+	let no_cookies = Request::new(b"GET / HTTP/1.1\r\n\r\n", sample_ip).unwrap();
+	assert_eq!(no_cookies.cookie("session"), None);
+}
+
+#[test]
+fn parse_http_version() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	let version = |raw: &[u8]| Request::new(raw, sample_ip).unwrap().version;
+
+	assert_eq!(version(b"GET / HTTP/1.0\r\n\r\n"), HttpVersion::V1_0);
 	assert_eq!(
-		Url::from(base),
-		Url {
-			path: vec![],
-			search_params: HashMap::new(),
-		}
+		version(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n"),
+		HttpVersion::V1_1
 	);
+	assert_eq!(version(b"GET / HTTP/1.1"), HttpVersion::V1_1);
+	assert_eq!(version(b"GET / HTTP/9\r\n\r\n"), HttpVersion::UNKNOWN);
+	assert_eq!(version(b"GET /\r\nX: a b\r\n\r\n"), HttpVersion::UNKNOWN);
+}
+
+#[test]
+fn wire_bytes_round_trip() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	let request = b"POST /a?b=c HTTP/1.0\r\nHost: localhost\r\nAccept: a\r\nAccept: b\r\nContent-Length: 4\r\n\r\nbody";
+
+	let parsed = Request::new(request, sample_ip).unwrap();
+	let bytes = parsed.to_wire_bytes();
+
+	assert!(bytes.starts_with(b"POST /a?b=c HTTP/1.0\r\n"));
+	assert!(bytes.ends_with(b"\r\n\r\nbody"));
+	assert_eq!(Request::new(&bytes, sample_ip).unwrap(), parsed);
+
+	let mut modified = parsed.clone();
+	modified.set_header("X-Forwarded-For", "127.0.0.1");
+	let reparsed = Request::new(&modified.to_wire_bytes(), sample_ip).unwrap();
+	assert_eq!(reparsed.get_header("X-Forwarded-For"), Some("127.0.0.1"));
+	assert_eq!(reparsed.get_headers("Accept"), vec!["a", "b"]);
+}
+
+#[test]
+fn keep_alive_defaults() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	let keep_alive = |raw: &[u8]| Request::new(raw, sample_ip).unwrap().wants_keep_alive();
 
+	assert!(keep_alive(b"GET / HTTP/1.1\r\n\r\n"));
+	assert!(!keep_alive(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n"));
+	assert!(!keep_alive(b"GET / HTTP/1.0\r\n\r\n"));
+	assert!(keep_alive(
+		b"GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n"
+	));
+	assert!(keep_alive(
+		b"GET / HTTP/1.0\r\nConnection: keep-alive, Upgrade\r\n\r\n"
+	));
+}
+
+#[test]
+fn case_insensitive_headers() {
+	let request = b"GET / HTTP/1.1\r\ncontent-type: text/plain\r\nX-CUSTOM: Value\r\n\r\n";
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	let mut parsed = Request::new(request, sample_ip).unwrap();
+
+	assert_eq!(parsed.get_header("Content-Type"), Some("text/plain"));
+	assert_eq!(parsed.get_header("x-custom"), Some("Value"));
+	assert!(parsed.has_header("X-Custom"));
+	assert_eq!(parsed.get_header_or("ACCEPT", "*/*"), "*/*");
+
+	parsed.set_header("X-Added", "1");
+	assert_eq!(parsed.get_header("x-added"), Some("1"));
+	assert!(parsed.headers.contains_key("x-added"));
+}
+
+#[test]
+fn repeated_headers() {
+	let request =
+		b"GET / HTTP/1.1\r\nAccept: text/html\r\nHost: localhost\r\naccept: application/json\r\n\r\n";
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	let parsed = Request::new(request, sample_ip).unwrap();
+
+	assert_eq!(parsed.get_header("Accept"), Some("text/html"));
 	assert_eq!(
-		Url::from(weird),
-		Url {
-			path: vec![],
-			search_params: HashMap::new()
-		}
+		parsed.get_headers("Accept"),
+		vec!["text/html", "application/json"]
 	);
+	assert_eq!(parsed.get_headers("Host"), vec!["localhost"]);
+	assert!(parsed.get_headers("X-Missing").is_empty());
 
+	// Values containing commas are kept whole.
+	let request = b"GET / HTTP/1.1\r\nSet-Cookie: a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT\r\nSet-Cookie: b=2\r\nCookie: a=1\r\nCookie: b=2\r\n\r\n";
+	let mut parsed = Request::new(request, sample_ip).unwrap();
 	assert_eq!(
-		Url::from(no_query),
-		Url {
-			path: vec!["a", "b", "c"],
-			search_params: HashMap::new()
-		}
+		parsed.get_headers("Set-Cookie"),
+		vec!["a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT", "b=2"]
 	);
+	assert_eq!(parsed.get_header("Cookie"), Some("a=1"));
+	assert_eq!(parsed.cookie("b"), Some("2"));
+
+	parsed.set_header("Cookie", "c=3");
+	assert_eq!(parsed.get_headers("Cookie"), vec!["c=3"]);
+}
+
+#[test]
+fn parse_all_cookies() {
+	let request = b"GET / HTTP/1.1\r\nCookie: a=1; b = %20two ; a=3; c=\r\n\r\n";
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	let parsed = Request::new(request, sample_ip).unwrap();
+
+	let mut expected = HashMap::new();
+	expected.insert("a".to_string(), "1".to_string());
+	expected.insert("b".to_string(), "%20two".to_string());
+	expected.insert("c".to_string(), "".to_string());
+	assert_eq!(parsed.cookies(), expected);
+
+	let no_cookies = Request::new(b"GET / HTTP/1.1\r\n\r\n", sample_ip).unwrap();
+	assert!(no_cookies.cookies().is_empty());
+}
+
+#[test]
+fn method_override() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+
+	let header = b"POST / HTTP/1.1\r\nX-HTTP-Method-Override: DELETE\r\n\r\n";
+	let parsed = Request::new(header, sample_ip).unwrap();
+	assert_eq!(parsed.method_override(), Some(Method::DELETE));
+
+	let form = b"POST / HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\n\r\nname=a&_method=delete";
+	let parsed = Request::new(form, sample_ip).unwrap();
+	assert_eq!(parsed.method_override(), Some(Method::DELETE));
+
+	let not_post = b"GET / HTTP/1.1\r\nX-HTTP-Method-Override: DELETE\r\n\r\n";
+	let parsed = Request::new(not_post, sample_ip).unwrap();
+	assert_eq!(parsed.method_override(), None);
+}
+
+#[test]
+fn parse_request_edge_cases() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+
+	// No headers, no body.
+	let parsed = Request::new(b"GET /a HTTP/1.1\r\n\r\n", sample_ip).unwrap();
+	assert_eq!(parsed.url, "/a");
+	assert!(parsed.headers.is_empty());
+	assert!(parsed.body.is_empty());
+
+	// Headers without the final empty line.
+	let parsed = Request::new(b"GET / HTTP/1.1\r\nX-A: B\r\n", sample_ip).unwrap();
+	assert_eq!(parsed.get_header("X-A"), Some("B"));
+	assert!(parsed.body.is_empty());
+
+	// Body containing its own `\r\n\r\n`.
+	let parsed = Request::new(b"POST / HTTP/1.1\r\nX-A: B\r\n\r\na\r\n\r\nb", sample_ip).unwrap();
+	assert_eq!(parsed.body, b"a\r\n\r\nb");
+
+	// Missing HTTP version.
+	assert_eq!(Request::new(b"GET /", sample_ip), None);
+
+	// Invalid header.
+	assert_eq!(
+		Request::new(b"GET / HTTP/1.1\r\nInvalid\r\n\r\n", sample_ip),
+		None
+	);
+}
+
+#[test]
+fn parse_url_array_notation() {
+	let url = Url::from("/list?a[]=1&a[]=2&obj[x]=3&b=4");
+
+	assert_eq!(url.search_params_all("a[]"), vec!["1", "2"]);
+	assert_eq!(url.search_params_all("b"), vec!["4"]);
+	assert!(url.search_params_all("c").is_empty());
+	assert_eq!(url.search_param("obj[x]"), Some("3"));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn parse_json_lines() {
+	let request = b"POST / HTTP/1.1\r\n\r\n{\"a\":1}\n\n{\"a\":2}\r\n{\"a\":3}\n";
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	let parsed = Request::new(request, sample_ip).unwrap();
+
+	let lines: Vec<serde_json::Value> = parsed.json_lines().collect::<Result<_, _>>().unwrap();
+
+	assert_eq!(
+		lines,
+		vec![
+			serde_json::json!({ "a": 1 }),
+			serde_json::json!({ "a": 2 }),
+			serde_json::json!({ "a": 3 }),
+		]
+	);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_or_default() {
+	use serde_json::Value;
+
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	let parse = |raw: &[u8]| Request::new(raw, sample_ip).unwrap();
+
+	let valid = parse(b"POST / HTTP/1.1\r\n\r\n{\"a\":1}");
+	assert_eq!(valid.json_or::<Value>(), serde_json::json!({ "a": 1 }));
+
+	let empty = parse(b"POST / HTTP/1.1\r\n\r\n");
+	assert_eq!(empty.json_or::<Value>(), Value::Null);
+
+	let invalid = parse(b"POST / HTTP/1.1\r\n\r\n{\"a\":");
+	assert_eq!(invalid.json_or::<Value>(), Value::Null);
+}
+
+#[test]
+fn limit_url_segments() {
+	let path = "/a".repeat(100_000);
+	let url = Url::from(path.as_str());
+	assert_eq!(url.path.len(), Url::MAX_PATH_SEGMENTS);
+
+	let query: Vec<String> = (0..100_000).map(|i| format!("k{}=v", i)).collect();
+	let raw = format!("/search?{}", query.join("&"));
+	let url = Url::from(raw.as_str());
+	assert_eq!(url.path, vec!["search"]);
+	assert_eq!(url.search_params.len(), Url::MAX_SEARCH_PARAMS);
+	assert_eq!(url.search_param("k0"), Some("v"));
+	assert_eq!(url.search_param("k99999"), None);
+}
+
+#[test]
+fn chunked_body_with_trailers() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	let raw =
+		b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\nTrailer: Content-MD5\r\n\r\n\
+		5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\nContent-MD5: abc123\r\nX-Signature: sig\r\n\r\n";
+
+	let req = Request::new(raw, sample_ip).unwrap();
+
+	assert_eq!(req.body, b"hello, world");
+	assert_eq!(req.trailer("Content-MD5"), Some("abc123"));
+	assert_eq!(req.trailer("X-Signature"), Some("sig"));
+	assert_eq!(
+		req.trailers(),
+		[
+			("content-md5".to_string(), "abc123".to_string()),
+			("x-signature".to_string(), "sig".to_string())
+		]
+	);
+	assert!(!req.has_header("Content-MD5"));
+	assert_eq!(req.get_header("Content-Length"), Some("12"));
+	assert!(!req.has_header("Transfer-Encoding"));
+
+	// Trailers can't change the framing, routing or auth headers.
+	let raw = b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n\
+		5\r\nhello\r\n0\r\nContent-Length: 999\r\nTransfer-Encoding: chunked\r\nHost: b\r\nAuthorization: x\r\n\r\n";
+	let req = Request::new(raw, sample_ip).unwrap();
+	assert_eq!(req.get_headers("Content-Length"), vec!["5"]);
+	assert!(!req.has_header("Transfer-Encoding"));
+	assert_eq!(req.get_headers("Host"), vec!["a"]);
+	assert!(!req.has_header("Authorization"));
+	assert_eq!(req.trailer("content-length"), Some("999"));
+
+	// Incomplete chunked bodies are kept as they are.
+	let raw = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel";
+	let req = Request::new(raw, sample_ip).unwrap();
+	assert_eq!(req.body, b"5\r\nhel");
+	assert!(req.has_header("Transfer-Encoding"));
+}
+
+#[test]
+fn owned_url_outlives_request() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	let request = Request::new(b"GET /a/b?c=d HTTP/1.1\r\n\r\n", sample_ip).unwrap();
+	let url = request.parse_url_owned();
+	drop(request);
+	assert_eq!(url.query(), "c=d");
+
+	let param = std::thread::spawn(move || url.search_param("c").map(String::from))
+		.join()
+		.unwrap();
+
+	assert_eq!(param.as_deref(), Some("d"));
+}
+
+#[test]
+fn detect_preflight() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+
+	let preflight = b"OPTIONS /a HTTP/1.1\r\nOrigin: https://example.com\r\nAccess-Control-Request-Method: PUT\r\n\r\n";
+	let parsed = Request::new(preflight, sample_ip).unwrap();
+	assert!(parsed.is_preflight());
+
+	let options = b"OPTIONS /a HTTP/1.1\r\nOrigin: https://example.com\r\n\r\n";
+	let parsed = Request::new(options, sample_ip).unwrap();
+	assert!(!parsed.is_preflight());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn parse_body_by_content_type() {
+	#[derive(serde::Deserialize, Debug, PartialEq)]
+	struct Login {
+		user: String,
+		remember: bool,
+	}
+
+	let expected = Login {
+		user: "snow board".into(),
+		remember: true,
+	};
+
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+
+	let json = b"POST / HTTP/1.1\r\nContent-Type: application/json\r\n\r\n{\"user\":\"snow board\",\"remember\":true}";
+	let parsed = Request::new(json, sample_ip).unwrap();
+	assert_eq!(parsed.body_typed::<Login>().unwrap(), expected);
+
+	let form = b"POST / HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded; charset=utf-8\r\n\r\nuser=snow+board&remember=true";
+	let parsed = Request::new(form, sample_ip).unwrap();
+	assert_eq!(parsed.body_typed::<Login>().unwrap(), expected);
+
+	let xml = b"POST / HTTP/1.1\r\nContent-Type: application/xml\r\n\r\n<user/>";
+	let parsed = Request::new(xml, sample_ip).unwrap();
+	assert_eq!(parsed.body_typed::<Login>().unwrap_err().status, 415);
+}
+
+#[test]
+fn detect_asset_urls() {
+	assert!(Url::from("/app.js").is_asset());
+	assert!(Url::from("/static/img/logo.min.png?v=2").is_asset());
+	assert!(!Url::from("/users/42").is_asset());
+	assert!(!Url::from("/").is_asset());
+	assert!(!Url::from("/.env").is_asset());
+	assert!(!Url::from("/v1.2/users").is_asset());
+}
+
+#[test]
+fn request_size_introspection() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+
+	let request = b"POST / HTTP/1.1\r\nHost: localhost\r\nX-A: b\r\n\r\nhello";
+	let parsed = Request::new(request, sample_ip).unwrap();
+
+	assert_eq!(parsed.header_count(), 2);
+	// "Host: localhost\r\n" + "X-A: b\r\n" + "hello"
+	assert_eq!(parsed.approx_size(), 17 + 8 + 5);
+}
+
+#[test]
+fn check_if_match() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+
+	let request = b"PUT /doc HTTP/1.1\r\nIf-Match: \"v1\", \"v2\"\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+	assert_eq!(parsed.if_match(), Some(vec!["\"v1\"", "\"v2\""]));
+	assert!(parsed.check_if_match("v2").is_ok());
+	assert_eq!(parsed.check_if_match("v3").unwrap_err().status, 412);
+
+	let request = b"PUT /doc HTTP/1.1\r\nIf-Match: W/\"v1\"\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+	assert_eq!(parsed.check_if_match("v1").unwrap_err().status, 412);
+
+	let request = b"PUT /doc HTTP/1.1\r\nIf-Match: *\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+	assert!(parsed.check_if_match("anything").is_ok());
+
+	let request = b"PUT /doc HTTP/1.1\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+	assert_eq!(parsed.if_match(), None);
+	assert!(parsed.check_if_match("v1").is_ok());
+}
+
+#[test]
+fn display_round_trips_search_params() {
+	let url = Url::from("/a?q=hello%20world&k=a%26b%3Dc");
+	let text = url.to_string();
+	assert!(!text.contains("%25"));
+
+	// Parsing it back gives the same values.
+	let parsed = Url::from(text.as_str());
+	assert_eq!(parsed.search_params.len(), 2);
+	assert_eq!(parsed.search_param_decoded("q").unwrap(), "hello world");
+	assert_eq!(parsed.search_param_decoded("k").unwrap(), "a&b=c");
+
+	// Raw values given to `Url::new` are encoded.
+	let url = Url::new(vec!["a"], map_into!("k" => "a&b=c"));
+	assert_eq!(url.to_string(), "a?k=a%26b%3Dc");
+
+	// `+` and `%` are kept as they are in plain values.
+	let url = Url::new(vec!["a"], map_into!("k" => "a+b", "p" => "100%25"));
+	let text = url.to_string();
+	let parsed = Url::from(text.as_str());
+	assert_eq!(parsed.search_param_decoded("k").unwrap(), "a+b");
+	assert_eq!(parsed.search_param_decoded("p").unwrap(), "100%25");
+	assert_eq!(url.to_encoded_string(), "/a?k=a%2Bb&p=100%2525");
+	assert_eq!(parsed.to_encoded_string(), url.to_encoded_string());
+
+	// Parsed values are encoded once, the same way by both serializations.
+	let url = Url::from("/x%20y?k=a+b&p=100%25");
+	assert_eq!(url.to_encoded_string(), "/x%20y?k=a%20b&p=100%25");
+	let text = url.to_string();
+	assert!(text.contains("k=a%20b") && text.contains("p=100%25"));
+	let parsed = Url::from(text.as_str());
+	assert_eq!(parsed.search_param_decoded("k").unwrap(), "a b");
+	assert_eq!(parsed.search_param_decoded("p").unwrap(), "100%");
+}
+
+#[test]
+fn same_origin_check() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+
+	let request = b"POST / HTTP/1.1\r\nOrigin: https://example.com\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+	assert_eq!(parsed.origin(), Some("https://example.com"));
+	assert!(parsed.is_same_origin("example.com"));
+
+	let request = b"POST / HTTP/1.1\r\nOrigin: https://evil.com\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+	assert!(!parsed.is_same_origin("example.com"));
+
+	let request = b"POST / HTTP/1.1\r\nReferer: http://example.com:8080/form?a=b\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+	assert_eq!(parsed.origin(), None);
+	assert!(parsed.is_same_origin("example.com:8080"));
+	assert!(!parsed.is_same_origin("example.com"));
+
+	let request = b"POST / HTTP/1.1\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+	assert!(!parsed.is_same_origin("example.com"));
+}
+
+#[test]
+fn parse_form_body() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+
+	let request = b"POST / HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\n\r\nname=snow+board&a=&msg=100%25%20fun&caf%C3%A9=%E2%98%83&bad=%zz&sign=%+A&neg=%-1";
+	let parsed = Request::new(request, sample_ip).unwrap();
+
+	let expected: HashMap<String, String> = map_into!(
+		"name" => "snow board",
+		"a" => "",
+		"msg" => "100% fun",
+		"café" => "☃",
+		"bad" => "%zz",
+		// Signs aren't hex digits, only `+` is decoded, as a space.
+		"sign" => "% A",
+		"neg" => "%-1",
+	);
+
+	assert_eq!(parsed.form(), expected);
+
+	let url = Url::from("/%+A/%-1");
+	assert_eq!(url.at_decoded(0).unwrap(), "%+A");
+	assert_eq!(url.at_decoded(1).unwrap(), "%-1");
+}
+
+#[test]
+fn merge_params() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+
+	let query_only = Request::new(b"GET /?a=1&b=two%20words HTTP/1.1\r\n\r\n", sample_ip).unwrap();
+	let expected: HashMap<String, String> = map_into!("a" => "1", "b" => "two words");
+	assert_eq!(query_only.params(), expected);
+
+	// Body values override the query.
+	let form = b"POST /?a=1&b=2 HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\n\r\nb=3&c=x+y";
+	let parsed = Request::new(form, sample_ip).unwrap();
+	let expected: HashMap<String, String> = map_into!("a" => "1", "b" => "3", "c" => "x y");
+	assert_eq!(parsed.params(), expected);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn merge_json_params() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+
+	let json = b"POST /?a=1&b=2 HTTP/1.1\r\nContent-Type: application/json\r\n\r\n{\"b\":\"text\",\"n\":3,\"ok\":true}";
+	let parsed = Request::new(json, sample_ip).unwrap();
+	let expected: HashMap<String, String> =
+		map_into!("a" => "1", "b" => "text", "n" => "3", "ok" => "true");
+	assert_eq!(parsed.params(), expected);
+
+	// Invalid bodies are ignored.
+	let invalid = b"POST /?a=1 HTTP/1.1\r\nContent-Type: application/json\r\n\r\n[1, 2]";
+	let parsed = Request::new(invalid, sample_ip).unwrap();
+	let expected: HashMap<String, String> = map_into!("a" => "1");
+	assert_eq!(parsed.params(), expected);
+}
+
+#[test]
+fn accept_best_media_type() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	let offered = ["text/html", "application/json"];
+
+	let request = b"GET / HTTP/1.1\r\nAccept: text/html;q=0.8, application/json\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+	assert_eq!(parsed.accept_best(&offered), Some("application/json"));
+
+	let request = b"GET / HTTP/1.1\r\nAccept: text/*;q=0.5, */*;q=0.1\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+	assert_eq!(parsed.accept_best(&offered), Some("text/html"));
+
+	let request = b"GET / HTTP/1.1\r\nAccept: image/png, text/html;q=0\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+	assert_eq!(parsed.accept_best(&offered), None);
+
+	let request = b"GET / HTTP/1.1\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+	assert_eq!(parsed.accept_best(&offered), Some("text/html"));
+}
+
+#[test]
+fn parse_multipart_body() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+
+	let mut request =
+		b"POST /upload HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=\"XyZ\"\r\n\r\n"
+			.to_vec();
+	request.extend_from_slice(
+		b"--XyZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nMy file\r\n",
+	);
+	request.extend_from_slice(b"--XyZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\nContent-Type: application/octet-stream\r\n\r\n");
+	request.extend_from_slice(&[0, 159, 255, b'\r', b'\n', 1]);
+	request.extend_from_slice(b"\r\n--XyZ--\r\n");
+
+	let parsed = Request::new(&request, sample_ip).unwrap();
+	let parts = parsed.multipart().unwrap();
+
+	assert_eq!(parts.len(), 2);
+	assert_eq!(parts[0].name, "title");
+	assert_eq!(parts[0].filename, None);
+	assert_eq!(parts[0].bytes, b"My file");
+
+	assert_eq!(parts[1].name, "file");
+	assert_eq!(parts[1].filename.as_deref(), Some("a.bin"));
+	assert_eq!(
+		parts[1].content_type.as_deref(),
+		Some("application/octet-stream")
+	);
+	assert_eq!(parts[1].bytes, [0, 159, 255, b'\r', b'\n', 1]);
+
+	// No boundary.
+	let request = b"POST / HTTP/1.1\r\nContent-Type: multipart/form-data\r\n\r\n--a\r\n";
+	assert_eq!(Request::new(request, sample_ip).unwrap().multipart(), None);
+
+	// Missing closing delimiter.
+	let request = b"POST / HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=a\r\n\r\n--a\r\nContent-Disposition: form-data; name=\"x\"\r\n\r\n1";
+	assert_eq!(Request::new(request, sample_ip).unwrap().multipart(), None);
+}
+
+#[test]
+fn decode_search_params() {
+	let url = Url::from("/search?q=hello%20world&x=a%2Bb&y=a+b&bad=100%&plain=done");
+
+	assert_eq!(url.search_param_decoded("q").unwrap(), "hello world");
+	assert_eq!(url.search_param_decoded("x").unwrap(), "a+b");
+	assert_eq!(url.search_param_decoded("y").unwrap(), "a b");
+	assert_eq!(url.search_param_decoded("bad").unwrap(), "100%");
+	assert_eq!(url.search_param_decoded("missing"), None);
+
+	// Values without escapes are borrowed.
+	let plain = url.search_param_decoded("plain").unwrap();
+	assert!(matches!(plain, std::borrow::Cow::Borrowed("done")));
+}
+
+#[test]
+fn plus_is_literal_in_paths() {
+	let url = Url::from("/a+b/c%20d?q=a+b");
+
+	assert_eq!(url.at_decoded(0).unwrap(), "a+b");
+	assert_eq!(url.at_decoded(1).unwrap(), "c d");
+	assert_eq!(url.at_decoded(2), None);
+	assert_eq!(url.search_param_decoded("q").unwrap(), "a b");
+}
+
+#[test]
+fn absolute_form_host() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+
+	let request = b"GET http://xn--e1afmkfd.example:8080/a/b?c=d HTTP/1.1\r\nHost: ignored\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+
+	assert_eq!(parsed.host(), Some("xn--e1afmkfd.example:8080"));
+
+	let url = parsed.parse_url();
+	assert_eq!(url.path, vec!["a", "b"]);
+	assert_eq!(url.search_param("c"), Some("d"));
+
+	let request = b"GET /a HTTP/1.1\r\nHost: xn--e1afmkfd.example\r\n\r\n";
+	let parsed = Request::new(request, sample_ip).unwrap();
+
+	assert_eq!(parsed.host(), Some("xn--e1afmkfd.example"));
+	assert_eq!(parsed.parse_url().path, vec!["a"]);
+}
+
+#[test]
+fn typed_search_params() {
+	let url = Url::from("/posts?page=2&ratio=0.5&draft=true&name=snow&neg=%2D3");
+
+	assert_eq!(url.search_param_as::<i32>("page"), Some(2));
+	assert_eq!(url.search_param_as::<f64>("ratio"), Some(0.5));
+	assert_eq!(url.search_param_as::<bool>("draft"), Some(true));
+	assert_eq!(url.search_param_as::<i32>("neg"), Some(-3));
+	assert_eq!(url.search_param_as::<i32>("name"), None);
+	assert_eq!(url.search_param_as::<i32>("missing"), None);
 }
@@ -1,4 +1,7 @@
-use snowboard::{headers, response, HttpVersion, Response};
+use snowboard::{
+	headers, mime_from_extension, response, Body, Cookie, HttpVersion, Method, Range, Request,
+	Response, SameSite, Url,
+};
 
 #[test]
 fn response_generation() {
@@ -40,3 +43,662 @@ fn response_generation() {
 		"HTTP/3.0 101 Switching Protocols\r\n\r\n"
 	);
 }
+
+#[test]
+fn clamp_unsupported_versions() {
+	let mut wire: Vec<u8> = vec![];
+	let mut res = response!(ok).with_version(HttpVersion::V1_0);
+	res.send_to(&mut wire).unwrap();
+
+	assert_eq!(res.wire_version(), HttpVersion::V1_0);
+	assert!(wire.starts_with(b"HTTP/1.0 200 Ok\r\n"));
+
+	// Sent as HTTP/1.1, with a warning printed to stderr.
+	let mut wire: Vec<u8> = vec![];
+	let mut res = response!(ok).with_version(HttpVersion::V2_0);
+	res.send_to(&mut wire).unwrap();
+
+	assert_eq!(res.version, HttpVersion::V2_0);
+	assert_eq!(res.wire_version(), HttpVersion::V1_1);
+	assert!(wire.starts_with(b"HTTP/1.1 200 Ok\r\n"));
+}
+
+#[test]
+fn early_hints_before_final_response() {
+	let mut wire: Vec<u8> = vec![];
+
+	Response::send_early_hints(&mut wire, &["</style.css>; rel=preload; as=style"]).unwrap();
+	response!(ok, "Hello").send_to(&mut wire).unwrap();
+
+	assert_eq!(
+		String::from_utf8(wire).unwrap(),
+		"HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload; as=style\r\n\r\nHTTP/1.1 200 Ok\r\n\r\nHello"
+	);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_error_envelope() {
+	let res = Response::json_error(404, "No such user");
+
+	assert_eq!(res.status, 404);
+	assert_eq!(res.status_text, "Not Found");
+
+	let body: serde_json::Value = serde_json::from_slice(&res.bytes).unwrap();
+	assert_eq!(body["error"]["code"], 404);
+	assert_eq!(body["error"]["message"], "No such user");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn serializable_json_response() {
+	use snowboard::{Json, ResponseLike};
+
+	#[derive(serde::Serialize)]
+	struct User {
+		name: &'static str,
+		age: u8,
+	}
+
+	let res = Json(User {
+		name: "snow",
+		age: 3,
+	})
+	.to_response();
+
+	assert_eq!(res.status, 200);
+	assert_eq!(res.bytes, br#"{"name":"snow","age":3}"#);
+	assert_eq!(
+		res.headers.unwrap().get("Content-Type").unwrap(),
+		"application/json; charset=utf-8"
+	);
+}
+
+#[test]
+fn iterate_headers() {
+	assert_eq!(response!(ok).headers_iter().count(), 0);
+
+	let res = response!(ok, [], headers! { "X-A" => "B" });
+	let headers: Vec<(&str, &str)> = res.headers_iter().collect();
+
+	assert_eq!(headers, vec![("X-A", "B")]);
+}
+
+#[test]
+fn set_multiple_cookies() {
+	let cookie = Cookie::new("session", "abc123")
+		.with_path("/")
+		.with_domain("example.com")
+		.with_max_age(3600)
+		.with_http_only()
+		.with_secure()
+		.with_same_site(SameSite::Strict);
+
+	let mut res = response!(ok).with_cookie("theme", "dark");
+	res.add_cookie(&cookie);
+
+	let cookies: Vec<&str> = res
+		.headers_iter()
+		.filter(|(key, _)| *key == "Set-Cookie")
+		.map(|(_, value)| value)
+		.collect();
+
+	assert_eq!(
+		cookies,
+		vec![
+			"theme=dark",
+			"session=abc123; Path=/; Domain=example.com; Max-Age=3600; HttpOnly; Secure; SameSite=Strict"
+		]
+	);
+
+	let text = res.to_string();
+	assert!(text.contains("Set-Cookie: theme=dark\r\nSet-Cookie: session=abc123; Path=/;"));
+}
+
+#[test]
+fn etag_not_modified() {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	let weak = Request::new(
+		b"GET / HTTP/1.1\r\nIf-None-Match: \"other\", W/\"abc\"\r\n\r\n",
+		sample_ip,
+	)
+	.unwrap();
+
+	let res = response!(ok, "body")
+		.with_etag("abc")
+		.or_not_modified(&weak);
+	assert_eq!(res.status, 304);
+	assert!(res.is_empty());
+	assert_eq!(res.headers.unwrap()["ETag"], "\"abc\"");
+
+	let res = response!(ok, "body")
+		.with_weak_etag("xyz")
+		.or_not_modified(&weak);
+	assert_eq!(res.status, 200);
+
+	let any = Request::new(b"GET / HTTP/1.1\r\nIf-None-Match: *\r\n\r\n", sample_ip).unwrap();
+	assert_eq!(response!(ok).or_not_modified(&any).status, 304);
+	assert_eq!(response!(not_found).or_not_modified(&any).status, 404);
+}
+
+#[test]
+fn content_range_headers() {
+	let partial = response!(partial_content, "0123456789").with_content_range(0, 9, 1000);
+	assert_eq!(partial.headers.unwrap()["Content-Range"], "bytes 0-9/1000");
+
+	let unsatisfiable = Response::unsatisfiable_range(1000);
+	assert_eq!(unsatisfiable.status, 416);
+	assert_eq!(
+		unsatisfiable.headers.unwrap()["Content-Range"],
+		"bytes */1000"
+	);
+}
+
+#[test]
+fn file_range_responses() {
+	let path = std::env::temp_dir().join(format!("snowboard-range-{}.txt", std::process::id()));
+	std::fs::write(&path, "0123456789").unwrap();
+
+	// Ranges are streamed, with one chunk for such small files.
+	let body = |mut res: Response| {
+		assert!(res.is_streamed());
+
+		let mut wire: Vec<u8> = vec![];
+		res.send_to(&mut wire).unwrap();
+
+		let wire = String::from_utf8(wire).unwrap();
+		let (_, chunks) = wire.split_once("\r\n\r\n").unwrap();
+		let (_, rest) = chunks.split_once("\r\n").unwrap();
+		rest.split_once("\r\n").unwrap().0.to_string()
+	};
+
+	let partial = Response::from_file_range(&path, Some(Range::Bounded(2, 5))).unwrap();
+	let headers = partial.headers.clone().unwrap();
+	assert_eq!(partial.status, 206);
+	assert_eq!(headers["Content-Range"], "bytes 2-5/10");
+	assert_eq!(headers["Content-Type"], "text/plain; charset=utf-8");
+	assert_eq!(body(partial), "2345");
+
+	let suffix = Response::from_file_range(&path, Some(Range::Suffix(3))).unwrap();
+	assert_eq!(body(suffix), "789");
+
+	let full = Response::from_file_range(&path, None).unwrap();
+	assert_eq!(full.status, 200);
+	assert_eq!(body(full), "0123456789");
+
+	let out_of_range = Response::from_file_range(&path, Some(Range::From(10))).unwrap();
+	assert_eq!(out_of_range.status, 416);
+	assert_eq!(out_of_range.headers.unwrap()["Content-Range"], "bytes */10");
+
+	std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn ranged_responses() {
+	let ip = "127.0.0.1:8080".parse().unwrap();
+	let ranged = |range: &str| {
+		let raw = format!("GET / HTTP/1.1\r\nRange: {}\r\n\r\n", range);
+		let req = Request::new(raw.as_bytes(), ip).unwrap();
+
+		let mut res = response!(ok, "0123456789");
+		res.set_content_length(res.len());
+		res.with_range(&req)
+	};
+
+	let partial = ranged("bytes=2-5");
+	let headers = partial.headers.clone().unwrap();
+	assert_eq!(partial.status, 206);
+	assert_eq!(partial.status_text, "Partial Content");
+	assert_eq!(partial.bytes, b"2345");
+	assert_eq!(headers["Content-Range"], "bytes 2-5/10");
+	assert_eq!(headers["Content-Length"], "4");
+	assert_eq!(headers["Accept-Ranges"], "bytes");
+
+	assert_eq!(ranged("bytes=-3").bytes, b"789");
+	assert_eq!(ranged("bytes=8-").bytes, b"89");
+
+	let out_of_range = ranged("bytes=10-20");
+	assert_eq!(out_of_range.status, 416);
+	assert_eq!(out_of_range.headers.unwrap()["Content-Range"], "bytes */10");
+
+	// Several ranges aren't supported, so the whole body is sent.
+	let multiple = ranged("bytes=0-1, 4-5");
+	assert_eq!(multiple.status, 200);
+	assert_eq!(multiple.bytes, b"0123456789");
+
+	assert_eq!(Range::parse("bytes=0-99"), Some(Range::Bounded(0, 99)));
+	assert_eq!(Range::parse("bytes=5-2"), None);
+	assert_eq!(Range::parse("items=0-1"), None);
+}
+
+#[test]
+fn mime_types_from_extensions() {
+	assert_eq!(mime_from_extension("html"), "text/html; charset=utf-8");
+	assert_eq!(mime_from_extension("JS"), "text/javascript; charset=utf-8");
+	assert_eq!(mime_from_extension("json"), "application/json");
+	assert_eq!(mime_from_extension("jpeg"), "image/jpeg");
+	assert_eq!(mime_from_extension("woff2"), "font/woff2");
+	assert_eq!(mime_from_extension("wasm"), "application/wasm");
+	assert_eq!(mime_from_extension(""), "application/octet-stream");
+	assert_eq!(mime_from_extension("exe"), "application/octet-stream");
+}
+
+#[test]
+fn file_responses() {
+	let dir = std::env::temp_dir().join(format!("snowboard-file-{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+
+	let cases = [
+		("index.html", "text/html; charset=utf-8"),
+		("style.CSS", "text/css; charset=utf-8"),
+		("logo.svg", "image/svg+xml"),
+		("data.bin", "application/octet-stream"),
+		("README", "application/octet-stream"),
+	];
+
+	for (name, content_type) in cases {
+		let path = dir.join(name);
+		std::fs::write(&path, "contents").unwrap();
+
+		let res = Response::file(&path).unwrap();
+		let headers = res.headers.clone().unwrap();
+
+		assert_eq!(res.status, 200);
+		assert_eq!(res.bytes, b"contents");
+		assert_eq!(headers.get("Content-Type").unwrap(), content_type);
+		assert_eq!(headers.get("Content-Length").unwrap(), "8");
+	}
+
+	let missing = Response::file(dir.join("missing.html")).unwrap_err();
+	assert_eq!(missing.kind(), std::io::ErrorKind::NotFound);
+
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn compress_with_preferred_encoding() {
+	use snowboard::Encoding;
+	use std::io::Read;
+
+	let body = "snowboard ".repeat(100);
+
+	let res = response!(ok, body.clone()).compress("gzip;q=0.5, br", &Encoding::DEFAULT_PRIORITY);
+	let headers = res.headers.clone().unwrap();
+	assert_eq!(headers.get("Content-Encoding").unwrap(), "br");
+	assert_eq!(headers.get("Vary").unwrap(), "Accept-Encoding");
+
+	let mut decoded = String::new();
+	brotli::Decompressor::new(res.bytes.as_slice(), 4096)
+		.read_to_string(&mut decoded)
+		.unwrap();
+	assert_eq!(decoded, body);
+
+	let res =
+		response!(ok, body.clone()).compress("deflate, gzip;q=0.8", &Encoding::DEFAULT_PRIORITY);
+	let headers = res.headers.clone().unwrap();
+	assert_eq!(headers.get("Content-Encoding").unwrap(), "deflate");
+
+	let mut decoded = String::new();
+	flate2::read::ZlibDecoder::new(res.bytes.as_slice())
+		.read_to_string(&mut decoded)
+		.unwrap();
+	assert_eq!(decoded, body);
+
+	// Equal quality values use the given priority.
+	let priority = [Encoding::Gzip, Encoding::Brotli];
+	assert_eq!(
+		Encoding::negotiate("br, gzip", &priority),
+		Some(Encoding::Gzip)
+	);
+	assert_eq!(
+		Encoding::negotiate("*;q=0.1, gzip;q=0", &priority),
+		Some(Encoding::Brotli)
+	);
+	assert_eq!(Encoding::negotiate("identity", &priority), None);
+
+	let res = response!(ok, body.clone()).compress("identity", &priority);
+	assert_eq!(res.bytes, body.as_bytes());
+	assert_eq!(res.headers, None);
+}
+
+#[test]
+fn debug_string_escapes_binary_body() {
+	let res = response!(ok, vec![b'a', 0, 0xff, b'\n']);
+	assert_eq!(
+		res.to_debug_string(),
+		"HTTP/1.1 200 Ok\r\n\r\na\\x00\\xff\\n"
+	);
+
+	let res = response!(ok, vec![b'x'; 300]);
+	let debug = res.to_debug_string();
+	assert!(debug.ends_with(&format!("{}... (44 more bytes)", "x".repeat(256))));
+}
+
+#[test]
+fn redirect_to_encoded_url() {
+	let mut params = std::collections::HashMap::new();
+	params.insert("q", "snow board&ski");
+	params.insert("page", "2");
+
+	let url = Url::new(vec!["search", "winter sports"], params);
+	let res = Response::redirect_to(&url);
+
+	assert_eq!(res.status, 303);
+	assert_eq!(
+		res.headers.unwrap().get("Location").unwrap(),
+		"/search/winter%20sports?page=2&q=snow%20board%26ski"
+	);
+
+	// Parsed URLs are already encoded, so they aren't encoded twice.
+	let res = Response::redirect_to(&Url::from("/winter%20sports?q=snow%20board"));
+	assert_eq!(
+		res.headers.unwrap().get("Location").unwrap(),
+		"/winter%20sports?q=snow%20board"
+	);
+}
+
+#[test]
+fn event_stream_frames() {
+	let (sender, receiver) = std::sync::mpsc::channel();
+	sender.send(("tick".into(), "1".into())).unwrap();
+	sender.send((String::new(), "two\nlines".into())).unwrap();
+	drop(sender);
+
+	let mut res = Response::event_stream(receiver);
+	let headers = res.headers.clone().unwrap();
+	assert_eq!(headers["Content-Type"], "text/event-stream");
+	assert_eq!(headers["Cache-Control"], "no-cache");
+
+	let mut wire: Vec<u8> = vec![];
+	res.send_to(&mut wire).unwrap();
+
+	// One chunk per event.
+	let wire = String::from_utf8(wire).unwrap();
+	assert!(wire.ends_with(
+		"\r\n\r\n15\r\nevent: tick\ndata: 1\n\n\r\n17\r\ndata: two\ndata: lines\n\n\r\n0\r\n\r\n"
+	));
+}
+
+#[test]
+fn streamed_body_is_chunked() {
+	let body: Vec<u8> = (0..20_000).map(|i| (i % 251) as u8).collect();
+	let mut res =
+		Response::from_reader(200, std::io::Cursor::new(body.clone())).with_default_headers();
+
+	assert!(res.is_streamed());
+	assert!(!res.is_empty());
+
+	let headers = res.headers.clone().unwrap();
+	assert_eq!(headers.get("Transfer-Encoding").unwrap(), "chunked");
+	assert!(!headers.contains_key("Content-Length"));
+
+	let mut wire: Vec<u8> = vec![];
+	res.send_to(&mut wire).unwrap();
+
+	let head_end = wire.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+	assert!(wire.starts_with(b"HTTP/1.1 200 Ok\r\n"));
+
+	// Decode the chunks.
+	let mut rest = &wire[head_end..];
+	let mut decoded = vec![];
+	let mut sizes = vec![];
+
+	loop {
+		let line_end = rest.windows(2).position(|w| w == b"\r\n").unwrap();
+		let size =
+			usize::from_str_radix(std::str::from_utf8(&rest[..line_end]).unwrap(), 16).unwrap();
+		rest = &rest[line_end + 2..];
+		sizes.push(size);
+
+		if size == 0 {
+			assert_eq!(rest, b"\r\n");
+			break;
+		}
+
+		decoded.extend_from_slice(&rest[..size]);
+		assert_eq!(&rest[size..size + 2], b"\r\n");
+		rest = &rest[size + 2..];
+	}
+
+	assert_eq!(decoded, body);
+	assert_eq!(sizes, vec![8192, 8192, 3616, 0]);
+}
+
+#[test]
+fn streamed_chunk_size() {
+	let mut res =
+		Response::from_reader(200, std::io::Cursor::new(vec![b'x'; 2500])).with_chunk_size(1000);
+
+	let mut wire: Vec<u8> = vec![];
+	res.send_to(&mut wire).unwrap();
+
+	let wire = String::from_utf8(wire).unwrap();
+	let body = &wire[wire.find("\r\n\r\n").unwrap() + 4..];
+	let expected = format!(
+		"3e8\r\n{}\r\n3e8\r\n{}\r\n1f4\r\n{}\r\n0\r\n\r\n",
+		"x".repeat(1000),
+		"x".repeat(1000),
+		"x".repeat(500)
+	);
+
+	assert_eq!(body, expected);
+}
+
+#[test]
+fn accel_redirect_header() {
+	let res = Response::accel_redirect("/protected/report.pdf");
+
+	assert_eq!(res.status, 200);
+	assert!(res.is_empty());
+	assert_eq!(
+		res.headers.unwrap().get("X-Accel-Redirect").unwrap(),
+		"/protected/report.pdf"
+	);
+}
+
+#[test]
+fn allow_header_from_methods() {
+	let res = response!(method_not_allowed).with_allow(&[
+		Method::GET,
+		Method::POST,
+		Method::GET,
+		Method::DELETE,
+		Method::POST,
+	]);
+
+	assert_eq!(res.headers.unwrap()["Allow"], "GET, POST, DELETE");
+	assert_eq!(Method::PATCH.as_str(), "PATCH");
+}
+
+#[test]
+fn io_result_responses() {
+	use snowboard::ResponseLike;
+	use std::io;
+
+	let res = std::fs::read("this/file/does/not/exist").to_response();
+	assert_eq!(res.status, 404);
+
+	let denied: io::Result<String> = Err(io::Error::new(io::ErrorKind::PermissionDenied, "nope"));
+	let res = denied.to_response();
+	assert_eq!(res.status, 403);
+	assert_eq!(res.bytes, b"nope");
+
+	let broken: io::Result<&str> = Err(io::Error::new(io::ErrorKind::Other, "disk on fire"));
+	let res = broken.to_response();
+	assert_eq!(res.status, 500);
+	assert_eq!(res.bytes, b"disk on fire");
+
+	let ok: io::Result<&str> = Ok("fine");
+	assert_eq!(ok.to_response().status, 200);
+}
+
+#[test]
+fn response_parts_round_trip() {
+	let res = response!(created, "done", headers! { "X-Id" => 5 });
+	let (status, status_text, headers, bytes) = res.clone().into_parts();
+
+	assert_eq!(status, 201);
+	assert_eq!(status_text, "Created");
+	assert_eq!(headers.as_ref().unwrap().get("X-Id").unwrap(), "5");
+	assert_eq!(bytes, Body::Bytes(b"done".to_vec()));
+
+	let rebuilt = Response::from_parts((status, status_text, headers, bytes));
+	assert_eq!(rebuilt, res);
+
+	// Streamed bodies are kept, so they're still sent in chunks.
+	let res = Response::from_reader(200, &b"streamed"[..]);
+	let mut parts = res.into_parts();
+	assert!(matches!(parts.3, Body::Stream(_)));
+	parts.2.as_mut().unwrap().insert("X-Mapped", "1".into());
+
+	let mut rebuilt = Response::from_parts(parts);
+	assert!(rebuilt.is_streamed());
+
+	let mut sent = vec![];
+	rebuilt.send_to(&mut sent).unwrap();
+	let sent = String::from_utf8(sent).unwrap();
+	assert!(sent.contains("Transfer-Encoding: chunked\r\n"));
+	assert!(sent.ends_with("\r\n\r\n8\r\nstreamed\r\n0\r\n\r\n"));
+}
+
+#[test]
+fn option_responses() {
+	use snowboard::ResponseLike;
+
+	let found = Some("user").to_response();
+	assert_eq!(found.status, 200);
+	assert_eq!(found.bytes, b"user");
+
+	let missing: Option<&str> = None;
+	let res = missing.to_response();
+	assert_eq!(res.status, 404);
+	assert!(res.is_empty());
+}
+
+#[test]
+fn response_builder() {
+	let res = Response::builder()
+		.status(418)
+		.header("X-A", "b")
+		.body("short and stout")
+		.build();
+
+	assert_eq!(res.status, 418);
+	assert_eq!(res.status_text, "I'm a teapot");
+	assert_eq!(res.headers.unwrap()["X-A"], "b");
+	assert_eq!(res.bytes, b"short and stout");
+
+	assert_eq!(Response::builder().build(), Response::default());
+	assert_eq!(
+		Response::builder()
+			.version(HttpVersion::V1_0)
+			.build()
+			.version,
+		HttpVersion::V1_0
+	);
+}
+
+#[test]
+fn reason_phrases() {
+	assert_eq!(snowboard::reason_phrase(200), "Ok");
+	assert_eq!(snowboard::reason_phrase(404), "Not Found");
+	assert_eq!(snowboard::reason_phrase(503), "Service Unavailable");
+	assert_eq!(snowboard::reason_phrase(999), "");
+}
+
+#[test]
+fn status_tuple_responses() {
+	use snowboard::ResponseLike;
+
+	let res = (201, "created").to_response();
+	assert_eq!(res.status, 201);
+	assert_eq!(res.status_text, "Created");
+	assert_eq!(res.bytes, b"created");
+
+	let res = (418, "tea", headers! { "X-A" => 1 }).to_response();
+	assert_eq!(res.status, 418);
+	assert_eq!(res.status_text, "I'm a teapot");
+	assert_eq!(res.headers.unwrap().get("X-A").unwrap(), "1");
+
+	assert_eq!((599, ()).to_response().status_text, "");
+}
+
+#[test]
+fn tuple_with_headers_response() {
+	use snowboard::ResponseLike;
+
+	let res = ("hi", headers! { "X-A" => 1 }).to_response();
+	assert_eq!(res.status, 200);
+	assert_eq!(res.bytes, b"hi");
+	assert_eq!(res.headers.unwrap().get("X-A").unwrap(), "1");
+
+	let base = response!(
+		created,
+		"made",
+		headers! { "X-A" => "old", "X-B" => "kept" }
+	);
+	let res = (base, headers! { "X-A" => "new" }).to_response();
+	let headers = res.headers.unwrap();
+
+	assert_eq!(res.status, 201);
+	assert_eq!(headers.get("X-A").unwrap(), "new");
+	assert_eq!(headers.get("X-B").unwrap(), "kept");
+	// Cookies from both are sent.
+	let base = response!(ok).with_cookie("a", "1");
+	let res = (base, headers! { "Set-Cookie" => "b=2" }).to_response();
+	assert_eq!(
+		res.headers_iter()
+			.filter(|(key, _)| *key == "Set-Cookie")
+			.count(),
+		2
+	);
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn manual_gzip_compression() {
+	use std::io::Read;
+
+	let body = "snowboard ".repeat(100);
+	let res = response!(ok, body.clone()).compress_gzip().unwrap();
+	let headers = res.headers.clone().unwrap();
+
+	assert_eq!(headers.get("Content-Encoding").unwrap(), "gzip");
+	assert_eq!(
+		headers.get("Content-Length").unwrap(),
+		&res.bytes.len().to_string()
+	);
+	assert_eq!(res.original_len(), body.len());
+
+	let mut decoded = String::new();
+	flate2::read::GzDecoder::new(res.bytes.as_slice())
+		.read_to_string(&mut decoded)
+		.unwrap();
+	assert_eq!(decoded, body);
+
+	// Compressing twice would need decoding twice.
+	assert!(res.compress_gzip().is_err());
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn compressed_body_lengths() {
+	use snowboard::Encoding;
+
+	let mut res = response!(ok, "snowboard ".repeat(100));
+	res.set_content_length(res.len());
+	assert_eq!(res.original_len(), 1000);
+	assert_eq!(res.encoded_len(), 1000);
+
+	let res = res.compress("gzip", &Encoding::DEFAULT_PRIORITY);
+	let headers = res.headers.clone().unwrap();
+
+	assert_eq!(res.original_len(), 1000);
+	assert_eq!(res.encoded_len(), res.bytes.len());
+	assert!(res.encoded_len() < 1000);
+	assert_eq!(
+		headers.get("Content-Length").unwrap(),
+		&res.encoded_len().to_string()
+	);
+}
@@ -0,0 +1,95 @@
+use snowboard::{response, Method, Request, Router};
+
+fn request(raw: &[u8]) -> Request {
+	let sample_ip = "127.0.0.1:8080".parse().unwrap();
+	Request::new(raw, sample_ip).unwrap()
+}
+
+fn router() -> Router {
+	Router::new()
+		.route(Method::GET, "/", |_, _| "home")
+		.route(Method::GET, "/users/:id", |_, params| {
+			format!("user {}", params["id"])
+		})
+		.route(Method::DELETE, "/users/:id", |_, params| {
+			format!("deleted {}", params["id"])
+		})
+		.route(Method::GET, "/users/:id/posts/:post", |_, params| {
+			format!("post {} of {}", params["post"], params["id"])
+		})
+}
+
+#[test]
+fn match_routes() {
+	let router = router();
+
+	let res = router.handle(request(b"GET / HTTP/1.1\r\n\r\n"));
+	assert_eq!(res.bytes, b"home");
+
+	let res = router.handle(request(b"GET /users/42?tab=posts HTTP/1.1\r\n\r\n"));
+	assert_eq!(res.bytes, b"user 42");
+
+	let res = router.handle(request(b"DELETE /users/42/ HTTP/1.1\r\n\r\n"));
+	assert_eq!(res.bytes, b"deleted 42");
+
+	let res = router.handle(request(b"GET /users/snow%20board/posts/7 HTTP/1.1\r\n\r\n"));
+	assert_eq!(res.bytes, b"post 7 of snow board");
+
+	// `+` only means a space in queries.
+	let res = router.handle(request(b"GET /users/a+b HTTP/1.1\r\n\r\n"));
+	assert_eq!(res.bytes, b"user a+b");
+}
+
+#[test]
+fn unmatched_routes() {
+	let router = router();
+
+	let res = router.handle(request(b"GET /missing HTTP/1.1\r\n\r\n"));
+	assert_eq!(res, response!(not_found));
+
+	let res = router.handle(request(b"PUT /users/42 HTTP/1.1\r\n\r\n"));
+	assert_eq!(res.status, 405);
+	assert!(res.is_empty());
+	assert_eq!(res.headers.unwrap().get("Allow").unwrap(), "GET, DELETE");
+
+	// Unmatched requests are given back.
+	let req = request(b"GET /users HTTP/1.1\r\n\r\n");
+	assert_eq!(router.dispatch(req.clone()), Err(req));
+}
+
+#[test]
+fn wildcard_and_param_segments() {
+	let router = Router::new()
+		.route(Method::GET, "/a/:x/b", |_, params| params["x"].clone())
+		.route(Method::GET, "/static/*rest", |_, params| {
+			format!("wildcard {}", params["rest"])
+		})
+		.route(Method::GET, "/static/:file", |_, params| {
+			format!("file {}", params["file"])
+		})
+		.route(Method::GET, "/users/:id", |_, _| "param")
+		.route(Method::GET, "/users/me", |_, _| "static");
+
+	let res = router.handle(request(b"GET /a/42/b HTTP/1.1\r\n\r\n"));
+	assert_eq!(res.bytes, b"42");
+
+	let res = router.handle(request(b"GET /a/42/c HTTP/1.1\r\n\r\n"));
+	assert_eq!(res.status, 404);
+
+	let res = router.handle(request(b"GET /static/css/app%20v2.css HTTP/1.1\r\n\r\n"));
+	assert_eq!(res.bytes, b"wildcard css/app v2.css");
+
+	// The wildcard needs at least one segment.
+	let res = router.handle(request(b"GET /static HTTP/1.1\r\n\r\n"));
+	assert_eq!(res.status, 404);
+
+	// More specific routes win, regardless of the order they were added in.
+	let res = router.handle(request(b"GET /static/app.css HTTP/1.1\r\n\r\n"));
+	assert_eq!(res.bytes, b"file app.css");
+
+	let res = router.handle(request(b"GET /users/me HTTP/1.1\r\n\r\n"));
+	assert_eq!(res.bytes, b"static");
+
+	let res = router.handle(request(b"GET /users/1 HTTP/1.1\r\n\r\n"));
+	assert_eq!(res.bytes, b"param");
+}
@@ -0,0 +1,1559 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use snowboard::{headers, response, Cors, Method, ReloadableHandler, Server, StreamExt};
+
+#[test]
+fn plaintext_has_no_tls_info() {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+	let (server_side, _) = listener.accept().unwrap();
+
+	assert_eq!(client.tls_info(), None);
+	assert_eq!(server_side.tls_info(), None);
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn request_timeout() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_request_timeout(Duration::from_millis(100));
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || {
+		server.run(|_| {
+			thread::sleep(Duration::from_millis(500));
+			response!(ok)
+		})
+	});
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.starts_with("HTTP/1.1 503 Service Unavailable"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn handler_pool_does_not_starve() {
+	let server = Server::new("127.0.0.1:0").unwrap().with_handler_pool(2);
+	let addr = server.addr().unwrap();
+	let slow = Duration::from_millis(500);
+
+	thread::spawn(move || {
+		server.run(move |req| {
+			if req.url == "/slow" {
+				// Busy loop, simulating a CPU-bound handler.
+				let start = Instant::now();
+				while start.elapsed() < slow {}
+			}
+
+			response!(ok)
+		})
+	});
+
+	let mut slow_client = TcpStream::connect(addr).unwrap();
+	slow_client
+		.write_all(b"GET /slow HTTP/1.1\r\n\r\n")
+		.unwrap();
+
+	let start = Instant::now();
+	let mut fast_client = TcpStream::connect(addr).unwrap();
+	fast_client
+		.write_all(b"GET /fast HTTP/1.1\r\n\r\n")
+		.unwrap();
+
+	let mut res = String::new();
+	fast_client.read_to_string(&mut res).unwrap();
+
+	assert!(res.starts_with("HTTP/1.1 200 Ok"));
+	assert!(start.elapsed() < slow);
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn serve_from_listener() {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap();
+	let server = Server::from_listener(listener);
+
+	thread::spawn(move || server.run(|_| "from listener"));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.ends_with("from listener"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn replace_handler_at_runtime() {
+	let server = Server::new("127.0.0.1:0").unwrap();
+	let addr = server.addr().unwrap();
+	let handler = ReloadableHandler::new(|_| "old");
+
+	let handle = handler.clone();
+	thread::spawn(move || server.run_reloadable(handle));
+
+	let get = || {
+		let mut client = TcpStream::connect(addr).unwrap();
+		client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+		let mut res = String::new();
+		client.read_to_string(&mut res).unwrap();
+		res
+	};
+
+	assert!(get().ends_with("old"));
+	handler.set_handler(|_| "new");
+	assert!(get().ends_with("new"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn answer_server_wide_options() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_server_options(&[Method::GET, Method::POST]);
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| response!(not_found)));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"OPTIONS * HTTP/1.1\r\n\r\n").unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.starts_with("HTTP/1.1 200 Ok"));
+	assert!(res.contains("Allow: GET, POST\r\n"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn cors_headers() {
+	let cors = Cors::default()
+		.with_origins(&["https://example.com"])
+		.with_methods(&[Method::GET, Method::PUT])
+		.with_headers(&["Content-Type"])
+		.with_max_age(600);
+
+	let server = Server::new("127.0.0.1:0").unwrap().with_cors(cors);
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| "hello"));
+
+	let send = |request: &[u8]| {
+		let mut client = TcpStream::connect(addr).unwrap();
+		client.write_all(request).unwrap();
+
+		let mut res = String::new();
+		client.read_to_string(&mut res).unwrap();
+		res
+	};
+
+	let preflight = send(b"OPTIONS /api HTTP/1.1\r\nOrigin: https://example.com\r\nAccess-Control-Request-Method: PUT\r\n\r\n");
+	assert!(preflight.starts_with("HTTP/1.1 204 No Content"));
+	assert!(preflight.contains("Access-Control-Allow-Origin: https://example.com\r\n"));
+	assert!(preflight.contains("Access-Control-Allow-Methods: GET, PUT\r\n"));
+	assert!(preflight.contains("Access-Control-Allow-Headers: Content-Type\r\n"));
+	assert!(preflight.contains("Access-Control-Max-Age: 600\r\n"));
+
+	let simple = send(b"GET /api HTTP/1.1\r\nOrigin: https://example.com\r\n\r\n");
+	assert!(simple.contains("Access-Control-Allow-Origin: https://example.com\r\n"));
+	assert!(simple.ends_with("hello"));
+
+	let other = send(b"GET /api HTTP/1.1\r\nOrigin: https://evil.com\r\n\r\n");
+	assert!(!other.contains("Access-Control-Allow-Origin"));
+}
+
+#[test]
+fn cors_keeps_vary() {
+	let cors = Cors::default().with_origins(&["https://example.com"]);
+	let origin = Some("https://example.com");
+
+	let res = cors.apply(
+		origin,
+		response!(ok).with_header("Vary", "Accept-Encoding".into()),
+	);
+	assert_eq!(res.headers.unwrap()["Vary"], "Accept-Encoding, Origin");
+
+	let res = cors.apply(origin, response!(ok).with_header("Vary", "origin".into()));
+	assert_eq!(res.headers.unwrap()["Vary"], "origin");
+
+	let res = cors.apply(origin, response!(ok));
+	assert_eq!(res.headers.unwrap()["Vary"], "Origin");
+}
+
+#[cfg(all(feature = "websocket", not(feature = "tls")))]
+#[test]
+fn malformed_websocket_upgrade() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.on_websocket("/ws", |_| {});
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| "not a websocket"));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client
+		.write_all(b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n")
+		.unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.starts_with("HTTP/1.1 400 Bad Request"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn keep_alive_header() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_keep_alive(Duration::from_secs(5), 3);
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| "hi"));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	let mut buffer = [0; 1024];
+
+	for max in [2, 1] {
+		client
+			.write_all(b"GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n")
+			.unwrap();
+
+		// The head and the body might arrive separately.
+		let mut res = String::new();
+		while !res.ends_with("hi") {
+			let len = client.read(&mut buffer).unwrap();
+			assert_ne!(len, 0, "connection closed early");
+			res += &String::from_utf8_lossy(&buffer[..len]);
+		}
+
+		assert!(res.contains("Connection: keep-alive\r\n"));
+		assert!(res.contains(&format!("Keep-Alive: timeout=5, max={}\r\n", max)));
+	}
+
+	// The last allowed request closes the connection.
+	client
+		.write_all(b"GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n")
+		.unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.contains("Connection: close\r\n"));
+	assert!(res.ends_with("hi"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn accept_timeout() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_accept_timeout(Duration::from_millis(50));
+	let addr = server.addr().unwrap();
+
+	let err = server.try_accept().unwrap_err();
+	assert_eq!(err.kind(), ErrorKind::TimedOut);
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET /late HTTP/1.1\r\n\r\n").unwrap();
+
+	let (_, request) = server.try_accept().unwrap();
+	assert_eq!(request.url, "/late");
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn abort_on_panic() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_abort_on_panic(true);
+	let addr = server.addr().unwrap();
+	let (sender, receiver) = std::sync::mpsc::channel();
+
+	thread::spawn(move || {
+		let result = server.checked_run(|req| {
+			if req.url == "/panic" {
+				panic!("handler bug");
+			}
+
+			response!(ok)
+		});
+
+		sender.send(result).unwrap();
+	});
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET /panic HTTP/1.1\r\n\r\n").unwrap();
+
+	let result = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+	assert!(result.is_err());
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn abort_on_panic_with_request_timeout() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_request_timeout(Duration::from_secs(5))
+		.with_abort_on_panic(true);
+	let addr = server.addr().unwrap();
+	let (sender, receiver) = std::sync::mpsc::channel();
+
+	thread::spawn(move || {
+		let result = server.checked_run(|req| {
+			if req.url == "/panic" {
+				panic!("handler bug");
+			}
+
+			response!(ok)
+		});
+
+		sender.send(result).unwrap();
+	});
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET /panic HTTP/1.1\r\n\r\n").unwrap();
+
+	// The panic isn't answered with a `503 Service Unavailable`, but stops the server.
+	let mut res = String::new();
+	let _ = client.read_to_string(&mut res);
+	assert!(!res.contains("503"));
+
+	let result = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+	assert!(result.is_err());
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn bound_address_callback() {
+	let (tx, rx) = std::sync::mpsc::channel();
+	let server = Server::new("127.0.0.1:0").unwrap();
+
+	thread::spawn(move || server.checked_run_with(|_| "bound", move |addr| tx.send(addr).unwrap()));
+
+	let addr = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+	assert_ne!(addr.port(), 0);
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.contains(" 200 "));
+	assert!(res.ends_with("bound"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn reject_http2_preface() {
+	let server = Server::new("127.0.0.1:0").unwrap();
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| response!(ok)));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client
+		.write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n")
+		.unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.starts_with("HTTP/1.1 505 HTTP Version Not Supported"));
+	assert!(res.ends_with("HTTP/2 is not supported, use HTTP/1.1 instead."));
+}
+
+#[cfg(all(unix, not(feature = "tls")))]
+#[test]
+fn hand_off_listener_fd() {
+	use std::os::unix::io::FromRawFd;
+
+	let server = Server::new("127.0.0.1:0").unwrap();
+	let addr = server.addr().unwrap();
+	let fd = server.into_listener_fd();
+
+	let listener = unsafe { TcpListener::from_raw_fd(fd) };
+	assert_eq!(listener.local_addr().unwrap(), addr);
+
+	let server = Server::from_listener(listener);
+	thread::spawn(move || server.run(|_| "restarted"));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.ends_with("restarted"));
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn redirect_to_tls_target() {
+	use snowboard::{Identity, TlsAcceptor};
+
+	let identity = Identity::from_pkcs12(include_bytes!("identity.pfx"), "snowboard").unwrap();
+	let acceptor = TlsAcceptor::new(identity).unwrap();
+
+	let server = Server::new_with_tls("127.0.0.1:0", acceptor)
+		.unwrap()
+		.with_tls_redirect_target("example.com", 8443);
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| response!(ok)));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET /a?b=c HTTP/1.1\r\n\r\n").unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.starts_with("HTTP/1.1 301 Moved Permanently"));
+	assert!(res.contains("Location: https://example.com:8443/a?b=c\r\n"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn header_timeout() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_header_timeout(Duration::from_millis(300));
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| response!(ok)));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	let start = Instant::now();
+
+	// Drip-feed part of the head, never finishing it.
+	for chunk in [&b"GET / HT"[..], b"TP/1.1\r\n", b"X-A: b\r\n"] {
+		client.write_all(chunk).unwrap();
+		thread::sleep(Duration::from_millis(50));
+	}
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.starts_with("HTTP/1.1 408 Request Timeout"));
+	assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn health_check_skips_handler() {
+	use std::sync::atomic::{AtomicBool, Ordering};
+	use std::sync::Arc;
+
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_health_check("/healthz");
+	let addr = server.addr().unwrap();
+
+	let called = Arc::new(AtomicBool::new(false));
+	let handler_called = Arc::clone(&called);
+
+	thread::spawn(move || {
+		server.run(move |_| {
+			handler_called.store(true, Ordering::SeqCst);
+			response!(ok, "app")
+		})
+	});
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET /healthz HTTP/1.1\r\n\r\n").unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.starts_with("HTTP/1.1 200 Ok"));
+	assert!(!called.load(Ordering::SeqCst));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn read_timeout() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_read_timeout(Duration::from_millis(100));
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| response!(ok, "served")));
+
+	// A client that never sends anything is disconnected.
+	let start = Instant::now();
+	let mut idle = TcpStream::connect(addr).unwrap();
+	idle.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+	let mut res = String::new();
+	idle.read_to_string(&mut res).unwrap();
+	assert_eq!(res, "");
+	assert!(start.elapsed() < Duration::from_secs(5));
+
+	// The server keeps accepting connections.
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+	assert!(res.ends_with("served"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn max_connections() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_keep_alive(Duration::from_secs(5), 10)
+		.with_max_connections(1);
+	let addr = server.addr().unwrap();
+	let limit = server.connection_limit().unwrap();
+
+	thread::spawn(move || server.run(|_| response!(ok, "served")));
+
+	// A kept-alive connection takes the only slot.
+	let mut first = TcpStream::connect(addr).unwrap();
+	first.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+	let mut buffer = [0; 1024];
+	let read = first.read(&mut buffer).unwrap();
+	assert!(String::from_utf8_lossy(&buffer[..read]).ends_with("served"));
+	assert_eq!(limit.max(), 1);
+	assert_eq!(limit.in_use(), 1);
+
+	let mut second = TcpStream::connect(addr).unwrap();
+	second
+		.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+		.unwrap();
+	second
+		.set_read_timeout(Some(Duration::from_millis(300)))
+		.unwrap();
+
+	// The second connection waits instead of being dropped.
+	let err = second.read(&mut buffer).unwrap_err();
+	assert!(matches!(
+		err.kind(),
+		ErrorKind::WouldBlock | ErrorKind::TimedOut
+	));
+
+	drop(first);
+	second
+		.set_read_timeout(Some(Duration::from_secs(5)))
+		.unwrap();
+
+	let mut res = String::new();
+	second.read_to_string(&mut res).unwrap();
+	assert!(res.ends_with("served"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn custom_upgrade() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.on_upgrade(|req, mut stream| {
+			if req.get_header("Upgrade") != Some("echo") {
+				let _ = response!(bad_request).send_to(&mut stream);
+				return;
+			}
+
+			let handshake = headers! { "Upgrade" => "echo", "Connection" => "Upgrade" };
+			if response!(switching_protocols, [], handshake)
+				.send_to(&mut stream)
+				.is_err()
+			{
+				return;
+			}
+
+			let mut buffer = [0; 1024];
+			while let Ok(read) = stream.read(&mut buffer) {
+				if read == 0 || stream.write_all(&buffer[..read]).is_err() {
+					break;
+				}
+			}
+		});
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| response!(ok, "handler")));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client
+		.write_all(b"GET /chat HTTP/1.1\r\nUpgrade: echo\r\nConnection: Upgrade\r\n\r\n")
+		.unwrap();
+
+	// Read the head of the handshake response.
+	let mut head = vec![];
+	let mut byte = [0; 1];
+	while !head.ends_with(b"\r\n\r\n") {
+		client.read_exact(&mut byte).unwrap();
+		head.push(byte[0]);
+	}
+
+	let head = String::from_utf8(head).unwrap();
+	assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+	assert!(head.contains("Upgrade: echo\r\n"));
+
+	// Raw bytes are echoed back.
+	for message in [&b"ping"[..], b"\x00\xffraw"] {
+		client.write_all(message).unwrap();
+
+		let mut echoed = vec![0; message.len()];
+		client.read_exact(&mut echoed).unwrap();
+		assert_eq!(echoed, message);
+	}
+
+	// Other requests still reach the handler.
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+	assert!(res.ends_with("handler"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn chunked_request_body() {
+	let server = Server::new("127.0.0.1:0").unwrap();
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || {
+		server.run(|req| {
+			let signature = req.trailer("X-Signature").unwrap_or("none").to_string();
+			response!(ok, format!("{} ({})", req.text(), signature))
+		})
+	});
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client
+		.write_all(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n")
+		.unwrap();
+
+	// The rest of the body arrives later.
+	thread::sleep(Duration::from_millis(100));
+	client
+		.write_all(b"6\r\n world\r\n0\r\nX-Signature: abc\r\n\r\n")
+		.unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+	assert!(res.ends_with("hello world (abc)"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn malformed_chunked_body() {
+	let server = Server::new("127.0.0.1:0").unwrap();
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| "unreachable"));
+
+	for chunk in [
+		"zz\r\nabc\r\n",
+		"ffffffffffffffff\r\nabc\r\n",
+		"3\r\nabcdef",
+	] {
+		let mut client = TcpStream::connect(addr).unwrap();
+		client
+			.set_read_timeout(Some(Duration::from_secs(5)))
+			.unwrap();
+
+		let raw = format!(
+			"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n{}",
+			chunk
+		);
+		client.write_all(raw.as_bytes()).unwrap();
+
+		// The connection is kept open, so this times out if the server waits for more.
+		let mut res = String::new();
+		client.read_to_string(&mut res).unwrap();
+
+		assert!(res.starts_with("HTTP/1.1 400 Bad Request"), "{}", chunk);
+		assert!(res.ends_with("Malformed chunked body"));
+	}
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn short_request_body() {
+	let server = Server::new("127.0.0.1:0").unwrap();
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| "unreachable"));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client
+		.set_read_timeout(Some(Duration::from_secs(5)))
+		.unwrap();
+	client
+		.write_all(b"POST / HTTP/1.1\r\nContent-Length: 100\r\n\r\nonly part of it")
+		.unwrap();
+	client.shutdown(std::net::Shutdown::Write).unwrap();
+
+	// Fails with a timeout if the server keeps waiting for the rest.
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.starts_with("HTTP/1.1 400 Bad Request"));
+	assert!(res.ends_with("Incomplete body"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn bytes_received() {
+	let server = Server::new("127.0.0.1:0").unwrap();
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || {
+		server.run(|req| response!(ok, format!("{} {}", req.bytes_received(), req.len())))
+	});
+
+	let raw = b"POST /log HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello";
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(raw).unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+	assert!(res.ends_with(&format!("{} 5", raw.len())));
+}
+
+#[cfg(all(feature = "tracing", not(feature = "tls")))]
+#[test]
+fn tracing_spans() {
+	use std::collections::BTreeMap;
+	use std::fmt;
+	use std::sync::atomic::{AtomicU64, Ordering};
+	use std::sync::{Arc, Mutex};
+	use tracing::field::{Field, Visit};
+	use tracing::span::{Attributes, Id, Record};
+	use tracing::{Event, Metadata};
+
+	type Fields = BTreeMap<&'static str, String>;
+
+	struct FieldVisitor<'a>(&'a mut Fields);
+
+	impl Visit for FieldVisitor<'_> {
+		fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+			self.0.insert(field.name(), format!("{:?}", value));
+		}
+	}
+
+	#[derive(Clone, Default)]
+	struct Recorder {
+		next_id: Arc<AtomicU64>,
+		spans: Arc<Mutex<BTreeMap<u64, (&'static str, Fields)>>>,
+		events: Arc<Mutex<Vec<Fields>>>,
+	}
+
+	impl tracing::Subscriber for Recorder {
+		fn enabled(&self, _: &Metadata<'_>) -> bool {
+			true
+		}
+
+		fn new_span(&self, span: &Attributes<'_>) -> Id {
+			let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+			let mut fields = Fields::new();
+			span.record(&mut FieldVisitor(&mut fields));
+
+			let name = span.metadata().name();
+			self.spans.lock().unwrap().insert(id, (name, fields));
+			Id::from_u64(id)
+		}
+
+		fn record(&self, span: &Id, values: &Record<'_>) {
+			if let Some((_, fields)) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+				values.record(&mut FieldVisitor(fields));
+			}
+		}
+
+		fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+		fn event(&self, event: &Event<'_>) {
+			let mut fields = Fields::new();
+			event.record(&mut FieldVisitor(&mut fields));
+			self.events.lock().unwrap().push(fields);
+		}
+
+		fn enter(&self, _: &Id) {}
+
+		fn exit(&self, _: &Id) {}
+	}
+
+	let recorder = Recorder::default();
+	tracing::subscriber::set_global_default(recorder.clone()).unwrap();
+
+	let server = Server::new("127.0.0.1:0").unwrap().with_tracing(true);
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| response!(created)));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client
+		.write_all(b"GET /traced?a=b HTTP/1.1\r\n\r\n")
+		.unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	let spans = recorder.spans.lock().unwrap();
+	let (name, fields) = spans
+		.values()
+		.find(|(_, fields)| fields.get("path").map(String::as_str) == Some("/traced"))
+		.unwrap();
+
+	assert_eq!(*name, "http.request");
+	assert_eq!(fields["method"], "GET");
+	assert_eq!(fields["status"], "201");
+
+	let ip = client.local_addr().unwrap().to_string();
+	let events = recorder.events.lock().unwrap();
+	let messages: Vec<&str> = events
+		.iter()
+		.filter(|fields| fields.get("ip") == Some(&ip))
+		.map(|fields| fields["message"].as_str())
+		.collect();
+
+	assert_eq!(messages, ["connection opened", "connection closed"]);
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn early_hints_before_response() {
+	let server =
+		Server::new("127.0.0.1:0")
+			.unwrap()
+			.with_early_hints(|req| match req.url.as_str() {
+				"/" => vec!["</style.css>; rel=preload; as=style".into()],
+				_ => vec![],
+			});
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| "page"));
+
+	let request = |raw: &[u8]| {
+		let mut client = TcpStream::connect(addr).unwrap();
+		client.write_all(raw).unwrap();
+
+		let mut res = String::new();
+		client.read_to_string(&mut res).unwrap();
+		res
+	};
+
+	let res = request(b"GET / HTTP/1.1\r\n\r\n");
+	let (hints, response) = res.split_once("\r\n\r\n").unwrap();
+	assert_eq!(
+		hints,
+		"HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload; as=style"
+	);
+	assert!(response.starts_with("HTTP/1.1 200 Ok\r\n"));
+	assert!(response.ends_with("page"));
+
+	// No links, or a client without interim responses.
+	assert!(request(b"GET /other HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 200 Ok\r\n"));
+	let res = request(b"GET / HTTP/1.0\r\n\r\n");
+	assert!(res.contains(" 200 Ok\r\n"));
+	assert!(!res.contains("103"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn metrics_endpoint() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_metrics_endpoint("/metrics");
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || {
+		server.run(|req| match req.url.as_str() {
+			"/" => response!(ok, "home"),
+			_ => response!(not_found),
+		})
+	});
+
+	let request = |raw: &[u8]| {
+		let mut client = TcpStream::connect(addr).unwrap();
+		client.write_all(raw).unwrap();
+
+		let mut res = String::new();
+		client.read_to_string(&mut res).unwrap();
+		res
+	};
+
+	request(b"GET / HTTP/1.1\r\n\r\n");
+	request(b"GET /missing HTTP/1.1\r\n\r\n");
+
+	let res = request(b"GET /metrics?format=text HTTP/1.1\r\n\r\n");
+	assert!(res.starts_with("HTTP/1.1 200 Ok"));
+	assert!(res.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+	assert!(res.contains("# TYPE http_requests_total counter\nhttp_requests_total 2\n"));
+	assert!(res.contains("http_responses_by_status{status=\"200\"} 1\n"));
+	assert!(res.contains("http_responses_by_status{status=\"404\"} 1\n"));
+	// Only the connection scraping the metrics.
+	assert!(res.contains("# TYPE http_active_connections gauge\nhttp_active_connections 1\n"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn max_connection_bytes() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_keep_alive(Duration::from_secs(5), 10)
+		.with_max_connection_bytes(100);
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| "hi"));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	let request = b"GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n";
+	let mut buffer = [0; 1024];
+
+	// Two requests of 42 bytes fit in the limit.
+	for _ in 0..2 {
+		client.write_all(request).unwrap();
+
+		let mut res = String::new();
+		while !res.ends_with("hi") {
+			let len = client.read(&mut buffer).unwrap();
+			assert_ne!(len, 0, "connection closed early");
+			res += &String::from_utf8_lossy(&buffer[..len]);
+		}
+
+		assert!(res.starts_with("HTTP/1.1 200 Ok"));
+	}
+
+	client.write_all(request).unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.starts_with("HTTP/1.1 413 Payload Too Large"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn read_body_using_content_length() {
+	let server = Server::new("127.0.0.1:0").unwrap().with_buffer_size(8192);
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|req| req.len().to_string()));
+
+	let body = "a".repeat(4000);
+	let mut client = TcpStream::connect(addr).unwrap();
+	client
+		.write_all(b"POST / HTTP/1.1\r\nContent-Length: 4000\r\n\r\n")
+		.unwrap();
+
+	// The body arrives after the head was already read.
+	thread::sleep(Duration::from_millis(50));
+	client.write_all(&body.as_bytes()[..1000]).unwrap();
+	thread::sleep(Duration::from_millis(50));
+	client.write_all(&body.as_bytes()[1000..]).unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.ends_with("4000"));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client
+		.write_all(b"POST / HTTP/1.1\r\nContent-Length: 10000\r\n\r\n")
+		.unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+
+	assert!(res.starts_with("HTTP/1.1 413 Payload Too Large"));
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn tls_min_version() {
+	use native_tls::TlsConnector;
+	use snowboard::{Identity, TlsVersion};
+
+	let identity = Identity::from_pkcs12(include_bytes!("identity.pfx"), "snowboard").unwrap();
+	let server = Server::new_with_identity("127.0.0.1:0", identity, TlsVersion::Tlsv12).unwrap();
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| "secure"));
+
+	let connect = |max_version| {
+		let connector = TlsConnector::builder()
+			.danger_accept_invalid_certs(true)
+			.min_protocol_version(Some(TlsVersion::Tlsv10))
+			.max_protocol_version(Some(max_version))
+			.build()
+			.unwrap();
+
+		connector.connect("localhost", TcpStream::connect(addr).unwrap())
+	};
+
+	assert!(connect(TlsVersion::Tlsv10).is_err());
+
+	let mut stream = connect(TlsVersion::Tlsv12).unwrap();
+	stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+	// The connection is closed without a TLS close notification,
+	// which some backends report as an error after reading everything.
+	let mut res = String::new();
+	let mut buffer = [0; 1024];
+	while let Ok(len @ 1..) = stream.read(&mut buffer) {
+		res += &String::from_utf8_lossy(&buffer[..len]);
+	}
+
+	assert!(res.ends_with("secure"));
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn tls_connection_has_tls_info() {
+	use native_tls::TlsConnector;
+	use snowboard::{Identity, TlsVersion};
+
+	let identity = Identity::from_pkcs12(include_bytes!("identity.pfx"), "snowboard").unwrap();
+	let server = Server::new_with_identity("127.0.0.1:0", identity, TlsVersion::Tlsv12).unwrap();
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || {
+		for (mut stream, _) in server {
+			let info = stream.tls_info();
+			let body = format!(
+				"tls: {}, client certificate: {}",
+				info.is_some(),
+				info.map_or(false, |info| info.peer_certificate.is_some())
+			);
+
+			let _ = response!(ok, body).send_to(&mut stream);
+		}
+	});
+
+	// `native_tls` can't report the negotiated protocol, so the client
+	// only allows TLS 1.2, which has to be the one used.
+	let connector = TlsConnector::builder()
+		.danger_accept_invalid_certs(true)
+		.min_protocol_version(Some(TlsVersion::Tlsv12))
+		.max_protocol_version(Some(TlsVersion::Tlsv12))
+		.build()
+		.unwrap();
+
+	let mut stream = connector
+		.connect("localhost", TcpStream::connect(addr).unwrap())
+		.unwrap();
+
+	// The client sees the certificate of the server.
+	let info = stream.tls_info().unwrap();
+	assert!(info.peer_certificate.is_some());
+
+	stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+	let mut res = String::new();
+	let mut buffer = [0; 1024];
+	while let Ok(len @ 1..) = stream.read(&mut buffer) {
+		res += &String::from_utf8_lossy(&buffer[..len]);
+	}
+
+	assert!(res.starts_with("HTTP/1.1 200 Ok"));
+	assert!(res.ends_with("tls: true, client certificate: false"));
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn sequential_tls_connections() {
+	use native_tls::TlsConnector;
+	use snowboard::{Identity, TlsAcceptor};
+
+	let identity = Identity::from_pkcs12(include_bytes!("identity.pfx"), "snowboard").unwrap();
+	let server = Server::new_with_tls("127.0.0.1:0", TlsAcceptor::new(identity).unwrap()).unwrap();
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| "resumed"));
+
+	// The same connector is reused, so the platform's TLS library
+	// can resume the first session if it supports it.
+	let connector = TlsConnector::builder()
+		.danger_accept_invalid_certs(true)
+		.build()
+		.unwrap();
+
+	for _ in 0..2 {
+		let mut stream = connector
+			.connect("localhost", TcpStream::connect(addr).unwrap())
+			.unwrap();
+		stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+		let mut res = String::new();
+		let mut buffer = [0; 1024];
+		while let Ok(len @ 1..) = stream.read(&mut buffer) {
+			res += &String::from_utf8_lossy(&buffer[..len]);
+		}
+
+		assert!(res.ends_with("resumed"));
+	}
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn map_every_response() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.map_response(|res| res.with_header("X-Powered-By", "snow".into()));
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || {
+		server.run(|req| match req.url.as_str() {
+			"/" => response!(ok, "home"),
+			_ => response!(not_found, "missing"),
+		})
+	});
+
+	for (path, status) in [("/", "200 Ok"), ("/missing", "404 Not Found")] {
+		let mut client = TcpStream::connect(addr).unwrap();
+		write!(client, "GET {} HTTP/1.1\r\n\r\n", path).unwrap();
+
+		let mut res = String::new();
+		client.read_to_string(&mut res).unwrap();
+
+		assert!(res.starts_with(&format!("HTTP/1.1 {}", status)));
+		assert!(res.contains("X-Powered-By: snow\r\n"));
+	}
+}
+
+#[cfg(all(feature = "compression", not(feature = "tls")))]
+#[test]
+fn compressed_content_length() {
+	use snowboard::Encoding;
+
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_compression(&Encoding::DEFAULT_PRIORITY);
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| "snowboard ".repeat(100)));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client
+		.write_all(b"GET / HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n")
+		.unwrap();
+
+	let mut res = vec![];
+	client.read_to_end(&mut res).unwrap();
+
+	let head_end = res.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+	let head = String::from_utf8_lossy(&res[..head_end]);
+	let body = &res[head_end + 4..];
+
+	assert!(head.contains("Content-Encoding: gzip\r\n"));
+	assert!(body.len() < 1000);
+	assert!(head.contains(&format!("Content-Length: {}\r\n", body.len())));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn connection_id() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_keep_alive(Duration::from_secs(5), 2)
+		.with_connection_id();
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|req| format!("[{}]", req.connection_id().unwrap())));
+
+	let mut first = TcpStream::connect(addr).unwrap();
+	let mut buffer = [0; 1024];
+	let mut ids = vec![];
+
+	for _ in 0..2 {
+		first
+			.write_all(b"GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n")
+			.unwrap();
+
+		let mut res = String::new();
+		while !res.ends_with(']') {
+			let len = first.read(&mut buffer).unwrap();
+			assert_ne!(len, 0, "connection closed early");
+			res += &String::from_utf8_lossy(&buffer[..len]);
+		}
+
+		ids.push(res.rsplit('[').next().unwrap().to_string());
+	}
+
+	assert_eq!(ids[0], ids[1]);
+
+	let mut second = TcpStream::connect(addr).unwrap();
+	second
+		.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+		.unwrap();
+
+	let mut res = String::new();
+	second.read_to_string(&mut res).unwrap();
+	assert_ne!(res.rsplit('[').next().unwrap(), ids[0]);
+}
+
+#[test]
+fn coalesced_response_write() {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+	let (mut server_side, _) = listener.accept().unwrap();
+
+	// Big enough to need more than one write.
+	let body: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+	let mut expected = response!(ok, body.clone());
+	let expected = expected.to_bytes();
+
+	let writer = thread::spawn(move || {
+		response!(ok, body)
+			.send_to_stream(&mut server_side)
+			.unwrap();
+	});
+
+	let mut received = vec![];
+	client.read_to_end(&mut received).unwrap();
+	writer.join().unwrap();
+
+	assert_eq!(received, expected);
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn custom_fallback_responses() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_not_found_handler(|req| response!(not_found, format!("No {}", req.url)))
+		.with_method_not_allowed_handler(|req| {
+			response!(method_not_allowed, format!("No {}", req.method))
+		});
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || {
+		server.run(|req| match (req.url.as_str(), req.method) {
+			("/", Method::GET) => response!(ok, "home"),
+			("/", _) => response!(method_not_allowed, [], headers! { "Allow" => "GET" }),
+			("/teapot", _) => response!(not_found, "handled"),
+			_ => response!(not_found),
+		})
+	});
+
+	let request = |raw: &[u8]| {
+		let mut client = TcpStream::connect(addr).unwrap();
+		client.write_all(raw).unwrap();
+
+		let mut res = String::new();
+		client.read_to_string(&mut res).unwrap();
+		res
+	};
+
+	let res = request(b"GET /missing HTTP/1.1\r\n\r\n");
+	assert!(res.starts_with("HTTP/1.1 404 Not Found"));
+	assert!(res.ends_with("No /missing"));
+
+	let res = request(b"DELETE / HTTP/1.1\r\n\r\n");
+	assert!(res.starts_with("HTTP/1.1 405 Method Not Allowed"));
+	assert!(res.contains("Allow: GET\r\n"));
+	assert!(res.ends_with("No DELETE"));
+
+	// Responses with a body are left untouched.
+	let res = request(b"GET /teapot HTTP/1.1\r\n\r\n");
+	assert!(res.ends_with("handled"));
+
+	let res = request(b"GET / HTTP/1.1\r\n\r\n");
+	assert!(res.ends_with("home"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn middleware_order() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_middleware(|req| {
+			if req.has_header("authorization") {
+				None
+			} else {
+				Some(response!(unauthorized))
+			}
+		})
+		.with_middleware(|req| {
+			req.set_header("x-trace", "first");
+			None
+		})
+		.with_middleware(|req| {
+			let trace = format!("{}, second", req.get_header_or("x-trace", ""));
+			req.set_header("x-trace", &trace);
+			None
+		})
+		.with_after_middleware(|res| {
+			res.set_header("X-After", "first".into());
+		})
+		.with_after_middleware(|res| {
+			let first = res.headers.as_ref().and_then(|h| h.get("X-After")).cloned();
+			res.set_header("X-After", format!("{}, second", first.unwrap_or_default()));
+		});
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || {
+		server.run(|req| response!(ok, req.get_header_or("x-trace", "none").to_string()))
+	});
+
+	let request = |raw: &[u8]| {
+		let mut client = TcpStream::connect(addr).unwrap();
+		client.write_all(raw).unwrap();
+
+		let mut res = String::new();
+		client.read_to_string(&mut res).unwrap();
+		res
+	};
+
+	let res = request(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+	assert!(res.starts_with("HTTP/1.1 401 Unauthorized"));
+	assert!(res.contains("X-After: first, second\r\n"));
+
+	let res = request(b"GET / HTTP/1.1\r\nAuthorization: yes\r\nConnection: close\r\n\r\n");
+	assert!(res.starts_with("HTTP/1.1 200 Ok"));
+	assert!(res.contains("X-After: first, second\r\n"));
+	assert!(res.ends_with("first, second"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn keep_alive_by_version() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_keep_alive(Duration::from_secs(5), 3);
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| "hi"));
+
+	// HTTP/1.1 connections are kept open without asking.
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+	let mut buffer = [0; 1024];
+	let mut res = String::new();
+	while !res.ends_with("hi") {
+		let len = client.read(&mut buffer).unwrap();
+		assert_ne!(len, 0, "connection closed early");
+		res += &String::from_utf8_lossy(&buffer[..len]);
+	}
+
+	assert!(res.contains("Connection: keep-alive\r\n"));
+
+	// HTTP/1.0 connections are closed unless asked otherwise.
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+	assert!(res.contains("Connection: close\r\n"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn max_head_size() {
+	let server = Server::new("127.0.0.1:0").unwrap().with_max_head_size(64);
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| "hi"));
+
+	let request = |raw: &[u8]| {
+		let mut client = TcpStream::connect(addr).unwrap();
+		client.write_all(raw).unwrap();
+
+		let mut res = String::new();
+		client.read_to_string(&mut res).unwrap();
+		res
+	};
+
+	let res = request(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+	assert!(res.starts_with("HTTP/1.1 200 Ok"));
+
+	// Exactly what's read before giving up, so the connection closes cleanly.
+	let long_headers = format!("GET / HTTP/1.1\r\nX-Long: {}", "a".repeat(68 - 24));
+	let res = request(long_headers.as_bytes());
+	assert!(res.starts_with("HTTP/1.1 431 Request Header Fields Too Large"));
+
+	let long_uri = format!("GET /{}", "a".repeat(68 - 5));
+	let res = request(long_uri.as_bytes());
+	assert!(res.starts_with("HTTP/1.1 414 URI Too Long"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn expect_continue() {
+	let server = Server::new("127.0.0.1:0").unwrap();
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|req| req.body));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client
+		.write_all(b"POST / HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n")
+		.unwrap();
+
+	// The body is only sent after the interim response.
+	let mut buffer = [0; 1024];
+	let mut res = String::new();
+	while !res.ends_with("\r\n\r\n") {
+		let len = client.read(&mut buffer).unwrap();
+		assert_ne!(len, 0, "connection closed early");
+		res += &String::from_utf8_lossy(&buffer[..len]);
+	}
+
+	assert_eq!(res, "HTTP/1.1 100 Continue\r\n\r\n");
+
+	client.write_all(b"hello").unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+	assert!(res.starts_with("HTTP/1.1 200 Ok"));
+	assert!(res.ends_with("hello"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn server_routes() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.route(Method::GET, "/users/:id", |_, params| {
+			format!("user {}", params["id"])
+		})
+		.with_method_not_allowed_handler(|_| response!(method_not_allowed, "nope"));
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|req| format!("fallback {}", req.url)));
+
+	let request = |raw: &[u8]| {
+		let mut client = TcpStream::connect(addr).unwrap();
+		client.write_all(raw).unwrap();
+
+		let mut res = String::new();
+		client.read_to_string(&mut res).unwrap();
+		res
+	};
+
+	assert!(request(b"GET /users/7 HTTP/1.1\r\n\r\n").ends_with("user 7"));
+	assert!(request(b"GET /other HTTP/1.1\r\n\r\n").ends_with("fallback /other"));
+
+	let res = request(b"POST /users/7 HTTP/1.1\r\n\r\n");
+	assert!(res.starts_with("HTTP/1.1 405 Method Not Allowed"));
+	assert!(res.contains("Allow: GET\r\n"));
+	assert!(res.ends_with("nope"));
+}
+
+#[cfg(not(feature = "tls"))]
+#[test]
+fn close_grace() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_keep_alive(Duration::from_millis(100), 5)
+		.with_close_grace(Duration::from_millis(500));
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run(|_| "hi"));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	let mut buffer = [0; 1024];
+
+	for _ in 0..2 {
+		client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+		let mut res = String::new();
+		while !res.ends_with("hi") {
+			let len = client.read(&mut buffer).unwrap();
+			assert_ne!(len, 0, "connection closed early");
+			res += &String::from_utf8_lossy(&buffer[..len]);
+		}
+
+		// Past the idle timeout, but within the grace window.
+		thread::sleep(Duration::from_millis(250));
+	}
+}
+
+#[cfg(all(feature = "async", not(feature = "tls")))]
+#[test]
+fn async_max_connection_bytes() {
+	let server = Server::new("127.0.0.1:0")
+		.unwrap()
+		.with_max_connection_bytes(30);
+	let addr = server.addr().unwrap();
+
+	thread::spawn(move || server.run_async(|_| async { "hi" }));
+
+	// Fits in the limit.
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+	assert!(res.starts_with("HTTP/1.1 200 Ok"));
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client
+		.write_all(b"GET / HTTP/1.1\r\nX-Padding: 0123456789\r\n\r\n")
+		.unwrap();
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+	assert!(res.starts_with("HTTP/1.1 413 Payload Too Large"));
+}
+
+#[cfg(all(feature = "async", not(feature = "tls")))]
+#[test]
+fn run_until_shutdown() {
+	use std::sync::mpsc;
+
+	let server = Server::new("127.0.0.1:0").unwrap();
+	let addr = server.addr().unwrap();
+
+	let (started, handler_started) = mpsc::channel();
+	let (shutdown, shutdown_requested) = mpsc::channel::<()>();
+	let (stopped, server_stopped) = mpsc::channel();
+
+	thread::spawn(move || {
+		let handler = move |_| {
+			let started = started.clone();
+
+			async move {
+				let _ = started.send(());
+				async_std::task::sleep(Duration::from_millis(200)).await;
+				"done"
+			}
+		};
+
+		let shutdown = async move {
+			let _ = async_std::task::spawn_blocking(move || shutdown_requested.recv()).await;
+		};
+
+		let _ = stopped.send(server.run_until(handler, shutdown));
+	});
+
+	let mut client = TcpStream::connect(addr).unwrap();
+	client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+	// Shut down while the request is being handled.
+	handler_started.recv().unwrap();
+	shutdown.send(()).unwrap();
+
+	let result = server_stopped.recv_timeout(Duration::from_secs(5)).unwrap();
+	assert!(result.is_ok());
+
+	let mut res = String::new();
+	client.read_to_string(&mut res).unwrap();
+	assert!(res.ends_with("done"));
+
+	// The listener is closed once the server stops.
+	assert!(TcpStream::connect(addr).is_err());
+}